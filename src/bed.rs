@@ -1,12 +1,58 @@
 use std::convert::TryFrom;
+use std::collections::HashMap;
+use crate::constant::WeekRange;
 use json::JsonValue;
 use crate::common::*;
+use std::error::Error;
+use simple_error::*;
 
 // Definition of a single bed, as loaded from params.json
 #[derive(Clone, Debug)]
 pub struct Bed {
     pub name: String,
     pub properties: Vec<String>,
+    // Growing area in square metres, given as "area" or as "length" and "width".
+    // Without one the bed is taken to be of the standard size, see Params::get_yield_scale
+    pub area: Option<f64>,
+    // The name of the whole bed, if this is one of its sections
+    pub section_of: Option<String>,
+}
+
+impl Bed {
+    // Parse a bed, split into its sections if it has more than one. Each section is
+    // planned as a bed of its own, named after the whole bed and its number, e.g. ~bA11/2,
+    // with an equal share of the bed's area
+    pub fn try_parse_sections(item: &JsonValue, standard_area: f64) -> Result<Vec<Bed>, Box<dyn Error>> {
+        let bed = Bed::try_from(item)?;
+        let num_sections = match &item["sections"] {
+            JsonValue::Null => 1,
+            sections => as_usize(sections)?
+        };
+        if num_sections == 0 {
+            bail!("Invalid bed {}: must have at least one section", bed.name);
+        }
+        if num_sections == 1 {
+            return Ok(vec![bed]);
+        }
+
+        let area = bed.area.unwrap_or(standard_area) / num_sections as f64;
+        Ok((1..=num_sections).map(|section| Bed {
+            name: format!("{}/{}", bed.name, section),
+            properties: bed.properties.clone(),
+            area: Some(area),
+            section_of: Some(bed.name.clone()),
+        }).collect())
+    }
+
+    #[cfg(test)]
+    pub fn dummy(name: &str) -> Self {
+        Bed {
+            name: name.to_string(),
+            properties: vec![],
+            area: None,
+            section_of: None,
+        }
+    }
 }
 
 impl TryFrom<&JsonValue> for Bed {
@@ -17,13 +63,57 @@ impl TryFrom<&JsonValue> for Bed {
             JsonValue::Array(arr) => arr.iter().map(|p| as_string(p)).collect::<Result<Vec<_>,_>>(),
             _ => Ok(vec![])
         }?;
+        let area = match (&item["area"], &item["length"], &item["width"]) {
+            (JsonValue::Null, JsonValue::Null, JsonValue::Null) => None,
+            (area, JsonValue::Null, JsonValue::Null) => Some(as_f64(area)?),
+            (JsonValue::Null, length, width) => Some(as_f64(length)? * as_f64(width)?),
+            _ => return Err("Invalid bed: give either an area or a length and width")
+        };
+        if area.is_some_and(|a| a <= 0.0) {
+            return Err("Invalid bed: area must be positive");
+        }
         Ok(Bed {
-            name: String::from(name),
-            properties: properties,
+            name,
+            properties,
+            area,
+            section_of: None,
         })
     }
 }
 
+// Weeks a bed must be left empty before a planting, to clear, compost and prepare it
+// Loaded from the "turnaround" section of params.json, with a number of weeks for every
+// bed and for beds with some property. Varieties can ask for more of their own
+#[derive(Clone, Debug, Default)]
+pub struct Turnaround {
+    pub weeks: WeekRange,
+    pub properties: HashMap<String, WeekRange>,
+}
+
+impl Turnaround {
+    pub fn try_parse(value: &JsonValue) -> Result<Self, Box<dyn Error>> {
+        let value_obj = as_object(value)?;
+        let mut turnaround = Turnaround::default();
+        if let Some(weeks) = value_obj.get("weeks") {
+            turnaround.weeks = as_usize(weeks)?;
+        }
+        if let Some(properties) = value_obj.get("properties") {
+            for (property, weeks) in as_object(properties)?.iter() {
+                turnaround.properties.insert(property.to_string(), as_usize(weeks)?);
+            }
+        }
+        Ok(turnaround)
+    }
+
+    // The turnaround for the bed, the longest of the global one and those of its properties
+    pub fn get_weeks(&self, bed: &Bed) -> WeekRange {
+        bed.properties
+            .iter()
+            .filter_map(|p| self.properties.get(p))
+            .fold(self.weeks, |a, &b| a.max(b))
+    }
+}
+
 #[cfg(test)]
 #[test]
 fn bed_from_json() {
@@ -37,5 +127,37 @@ fn bed_from_json() {
     assert!(bed.properties.contains(&String::from("prop1")));
     assert!(bed.properties.contains(&String::from("prop2")));
     assert!(!bed.properties.contains(&String::from("prop3")));
+    assert_eq!(bed.area, None);
+
+    let js = json::parse(r#"{ "name": "~b01", "length": 20, "width": 0.75 }"#).expect("test is wrong");
+    assert_eq!(Bed::try_from(&js).expect("failed to parse").area, Some(15.0));
+    let js = json::parse(r#"{ "name": "~b02", "area": 10, "length": 20 }"#).expect("test is wrong");
+    Bed::try_from(&js).expect_err("area and length");
 }
 
+
+#[cfg(test)]
+#[test]
+fn bed_sections_from_json() {
+    let js = json::parse(r#"{ "name": "~bA11", "area": 30, "sections": 3, "properties": [ "prop1" ] }"#).expect("test is wrong");
+    let sections = Bed::try_parse_sections(&js, 30.0).expect("failed to parse");
+    assert_eq!(sections.iter().map(|b| b.name.as_str()).collect::<Vec<_>>(), vec!["~bA11/1", "~bA11/2", "~bA11/3"]);
+    assert_eq!(sections[1].area, Some(10.0));
+    assert_eq!(sections[1].section_of.as_deref(), Some("~bA11"));
+    assert!(sections[2].properties.contains(&String::from("prop1")));
+
+    let js = json::parse(r#"{ "name": "~bA12" }"#).expect("test is wrong");
+    let sections = Bed::try_parse_sections(&js, 30.0).expect("failed to parse");
+    assert_eq!(sections.len(), 1);
+    assert_eq!(sections[0].section_of, None);
+}
+
+#[cfg(test)]
+#[test]
+fn turnaround_from_json() {
+    let js = json::parse(r#"{ "weeks": 1, "properties": { "polytunnel": 2 } }"#).expect("test is wrong");
+    let turnaround = Turnaround::try_parse(&js).expect("failed to parse");
+    let bed = |properties: Vec<&str>| Bed{ properties: properties.iter().map(|p| p.to_string()).collect(), ..Bed::dummy("~b00") };
+    assert_eq!(turnaround.get_weeks(&bed(vec![])), 1);
+    assert_eq!(turnaround.get_weeks(&bed(vec!["outdoor", "polytunnel"])), 2);
+}
@@ -5,6 +5,8 @@ use crate::constant::WeekId;
 use crate::constant::{VarietyId, SEASON_LENGTH};
 use crate::params::Params;
 use crate::bed::Bed;
+use crate::events::Actual;
+use crate::nursery::Sowing;
 
 // Represents part of a crop plan relating to a single bed
 // Provides methods to extract instructions and statistics and print
@@ -13,62 +15,165 @@ pub struct BedPlan<'a> {
     plan: &'a Plan,
     plan_previous_year: &'a Plan,
     params: &'a Params,
-    def: &'a Bed
+    def: &'a Bed,
+    // Harvest schedules are for a bed of the standard size, see Params::get_yield_scale
+    yield_scale: f64,
+    // Weeks the bed is left empty before a planting, unless the variety needs longer
+    turnaround: usize,
+    // The longest turnaround of any variety in the bed
+    max_turnaround: usize
 }
 
 impl BedPlan<'_> {
     pub fn new<'a>(bed: usize, plan: &'a Plan, params: &'a Params) -> BedPlan<'a> {
+        let turnaround = params.turnaround.get_weeks(&params.beds[bed]);
         BedPlan{
-            bed: bed,
-            plan: plan,
+            bed,
+            plan,
             plan_previous_year: &params.plan_previous_year,
-            params: params,
-            def: &params.beds[bed]
+            params,
+            def: &params.beds[bed],
+            yield_scale: params.get_yield_scale(bed),
+            turnaround,
+            max_turnaround: params.varieties.iter().fold(turnaround, |a, v| a.max(v.turnaround))
         }
     }
 
+    fn get_turnaround(&self, variety: VarietyId) -> usize {
+        self.turnaround.max(self.params.varieties[variety].turnaround)
+    }
+
     pub fn iter<'a>(&'a self) -> BedPlanIterator<'a> {
-        BedPlanIterator::new(&self)
+        BedPlanIterator::new(self)
     }
 
     pub fn utilization(&self) -> f32 {
 
         let mut occupied_weeks = 0.0;
         for bed_week in self.iter().take(SEASON_LENGTH) {
-            match bed_week.get_growing_variety() {
-                Some(_) => occupied_weeks += 1.0,
-                None => ()
-            }
+            if bed_week.get_growing_variety().is_some() { occupied_weeks += 1.0 }
         }
         occupied_weeks / SEASON_LENGTH as f32
     }
 
+    // The value to the soil of the crops grown in the bed over the season, see Variety::soil_benefit
+    pub fn soil_benefit(&self) -> f64 {
+        let mut soil_benefit = 0;
+        for bed_week in self.iter().take(SEASON_LENGTH) {
+            if let Some(variety) = bed_week.get_growing_variety() {
+                soil_benefit += variety.soil_benefit;
+            }
+        }
+        soil_benefit as f64 * self.yield_scale
+    }
+
     pub fn write_instructions(&self, tasks: &mut Tasks) {
+        let mut previous_crop = 0;
         for bed_week in self.iter().take(SEASON_LENGTH) {
+            self.write_incorporating_instructions(&bed_week, previous_crop, tasks);
+            self.write_turnaround_instructions(&bed_week, previous_crop, tasks);
             self.write_planting_instructions(&bed_week, tasks);
             self.write_harvesting_instructions(&bed_week, tasks);
+            previous_crop = bed_week.crop;
+        }
+    }
+
+    // The "incorporate" instruction of a crop is written in the week it comes out, whether it
+    // is over or a planting replaces it, for digging in green manures and crop residues
+    fn write_incorporating_instructions(&self, bed_week: &BedWeek, previous_crop: VarietyId, tasks: &mut Tasks) {
+        let is_over = bed_week.crop != previous_crop || bed_week.crop_age == 0;
+        if previous_crop == 0 || !is_over {
+            return;
+        }
+        let variety = &self.params.varieties[previous_crop];
+        if let Some(instruction_template) = variety.instructions.get("incorporate") {
+            let instruction = instruction_template
+                .replace("<variety>", &variety.name)
+                .replace("<bed>", &self.def.name);
+            tasks.add(bed_week.week, &instruction);
+        }
+    }
+
+    // Clear the bed when a crop comes out, and prepare it the week before a planting,
+    // if beds are given time to turn around
+    fn write_turnaround_instructions(&self, bed_week: &BedWeek, previous_crop: VarietyId, tasks: &mut Tasks) {
+        if self.max_turnaround == 0 {
+            return;
+        }
+        if bed_week.cleared_early != 0 {
+            let instruction = format!("Clear bed {} of {} early, before its harvests are over", self.def.name, self.params.varieties[bed_week.cleared_early].name);
+            tasks.add(bed_week.week, &instruction);
+        } else if bed_week.crop == 0 && previous_crop != 0 {
+            let instruction = format!("Clear bed {} of {}", self.def.name, self.params.varieties[previous_crop].name);
+            tasks.add(bed_week.week, &instruction);
+        }
+        // A planting in the first week is prepared for at the end of the season before
+        if let Some(planted_variety) = bed_week.get_planted_variety() {
+            if self.get_turnaround(bed_week.crop) > 0 && bed_week.week > 0 {
+                let instruction = format!("Prepare bed {} for {}", self.def.name, planted_variety.name);
+                tasks.add(bed_week.week - 1, &instruction);
+            }
         }
     }
 
     fn write_planting_instructions(&self, bed_week: &BedWeek, tasks: &mut Tasks) {
-        match bed_week.get_planted_variety() {
-            Some(planted_variety) => {
-                for week_offset in -52..52 {
-                    let instruction_template_opt = &planted_variety.instructions.get(&week_offset.to_string());
-                    if let Some(instruction_template) = instruction_template_opt {
-                        let instruction = instruction_template
-                            .replace("<variety>", &planted_variety.name)
-                            .replace("<label>", &format!("{}-{}", self.def.name, bed_week.week))
-                            .replace("<bed>", &self.def.name);
-                        let mut week = week_offset + (bed_week.week as i32);
-                        while week < 0 {
-                            week += SEASON_LENGTH as i32
-                        }
-                        tasks.add(week as usize, &instruction);
+        if let Some(planted_variety) = bed_week.get_planted_variety() {
+            for week_offset in -52..52 {
+                let instruction_template_opt = &planted_variety.instructions.get(&week_offset.to_string());
+                if let Some(instruction_template) = instruction_template_opt {
+                    let instruction = instruction_template
+                        .replace("<variety>", &planted_variety.name)
+                        .replace("<label>", &format!("{}-{}", self.def.name, bed_week.week))
+                        .replace("<bed>", &self.def.name);
+                    let mut week = week_offset + (bed_week.week as i32);
+                    while week < 0 {
+                        week += SEASON_LENGTH as i32
+                    }
+                    tasks.add(week as usize, &instruction);
+                };
+            }
+        }
+    }
+
+    // Add the minutes of work the bed takes to the workload of each week, counted the same
+    // way as the instructions are written
+    pub fn add_workload(&self, workload: &mut [f64]) {
+        for bed_week in self.iter().take(SEASON_LENGTH) {
+            if let Some(planted_variety) = bed_week.get_planted_variety() {
+                for (key, labour) in planted_variety.labour.iter() {
+                    let week_offset = match key.parse::<i32>() {
+                        Ok(week_offset) if (-52..52).contains(&week_offset) => week_offset,
+                        _ => continue
                     };
+                    let week = (week_offset + bed_week.week as i32).rem_euclid(SEASON_LENGTH as i32);
+                    workload[week as usize] += labour.get_minutes(self.yield_scale, 0);
+                }
+            }
+            if bed_week.harvestable_units != 0 {
+                if let Some(labour) = bed_week.get_growing_variety().unwrap().labour.get("harvest") {
+                    workload[bed_week.week] += labour.get_minutes(self.yield_scale, bed_week.harvestable_units);
                 }
-            },
-            None => ()
+            }
+        }
+    }
+
+    // Add the plantings raised in the nursery to the sowings
+    pub fn add_sowings(&self, sowings: &mut Vec<Sowing>) {
+        for bed_week in self.iter().take(SEASON_LENGTH) {
+            let planted_variety = match bed_week.get_planted_variety() {
+                Some(planted_variety) => planted_variety,
+                None => continue
+            };
+            if let Some(propagation) = &planted_variety.propagation {
+                let weeks = planted_variety.get_nursery_weeks();
+                sowings.push(Sowing {
+                    bed: self.bed,
+                    variety: bed_week.crop,
+                    trays: propagation.trays_per_bed * self.yield_scale,
+                    from_week: (bed_week.week + SEASON_LENGTH - weeks) % SEASON_LENGTH,
+                    weeks,
+                });
+            }
         }
     }
 
@@ -92,6 +197,32 @@ impl BedPlan<'_> {
         self.plan.get(self.bed, week)
     }
 
+    fn get_actual(&self, week: usize) -> Option<Actual> {
+        self.params.actuals.get(self.bed, week)
+    }
+
+    // The week of the planned planting that a planting of the variety logged in the week was
+    // moved from, whether brought forward or planted late. That is the planned planting of the
+    // variety nearest to the logged one within the life of the crop, the earlier of two as near
+    fn get_moved_planting_week(&self, variety: VarietyId, week: usize) -> Option<usize> {
+        let longevity = self.params.varieties[variety].get_longevity();
+        ((week + 1).saturating_sub(longevity)..std::cmp::min(week + longevity, SEASON_LENGTH))
+            .filter(|&w| w != week && self.get_variety(w) == variety && self.get_actual(w).is_none())
+            .min_by_key(|&w| (w as i32 - week as i32).abs())
+    }
+
+    // Whether the planned planting in the week was logged as planted in another week instead
+    fn is_moved(&self, week: usize) -> bool {
+        let variety = self.get_variety(week);
+        if variety == 0 || self.get_actual(week).is_some() {
+            return false;
+        }
+        let longevity = self.params.varieties[variety].get_longevity();
+        ((week + 1).saturating_sub(longevity)..std::cmp::min(week + longevity, SEASON_LENGTH))
+            .filter(|&w| self.get_actual(w) == Some(Actual::Planted(variety)))
+            .any(|w| self.get_moved_planting_week(variety, w) == Some(week))
+    }
+
     fn get_last_planting_week_previous_year(&self) -> Option<usize> {
         self.plan_previous_year.get_last_planting_week(self.bed)
     }
@@ -103,14 +234,28 @@ impl BedPlan<'_> {
 
 impl std::fmt::Display for BedPlan<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
-        writeln!(f, "Bed {}\n", self.def.name)?;
-        writeln!(f, "{:<9}{:<9}", "Week", "Variety")?;
-        for week in 0..SEASON_LENGTH {
-            let variety = self.get_variety(week);
-            if variety != 0 {
-                let variety_name = &self.params.varieties[variety].name;
-                println!("{:<9}{:<9}", week, variety_name);
+        match self.def.area {
+            Some(area) => writeln!(f, "Bed {} ({} m²)\n", self.def.name, area)?,
+            None => writeln!(f, "Bed {}\n", self.def.name)?
+        }
+        writeln!(f, "{:<9}{:<25}{:<9}", "Week", "Variety", "Note")?;
+        let cleared_early = self.iter().take(SEASON_LENGTH).map(|w| w.cleared_early).collect::<Vec<_>>();
+        for (week, &cleared) in cleared_early.iter().enumerate() {
+            if cleared != 0 {
+                writeln!(f, "{:<9}{:<25}{:<9}", week, self.params.varieties[cleared].name, "cleared early")?;
             }
+            let variety = self.get_variety(week);
+            let (variety, note) = match self.get_actual(week) {
+                Some(Actual::Planted(actual_variety)) => (actual_variety, "planted"),
+                Some(Actual::Failed) => (variety, "failed"),
+                Some(Actual::Removed) => (variety, "removed"),
+                None if variety == 0 => continue,
+                None if self.is_moved(week) => (variety, "moved"),
+                None if self.params.is_pinned(self.bed, week) => (variety, "pinned"),
+                None => (variety, "")
+            };
+            let variety_name = if variety != 0 { &self.params.varieties[variety].name } else { "" };
+            writeln!(f, "{:<9}{:<25}{:<9}", week, variety_name, note)?;
         }
         writeln!(f)?;
         writeln!(f, "Utilization: {:.0}%", self.utilization() * 100.0)?;
@@ -123,6 +268,11 @@ pub struct BedWeek<'a> {
     pub crop: VarietyId,
     pub crop_age: usize,
     pub harvestable_units: i32,
+    // The crop in the bed before the growing one, or the last crop if the bed is empty
+    pub predecessor: VarietyId,
+    // The crop taken out this week before its harvests are over, to leave the bed empty for
+    // the turnaround before the next planting, or 0
+    pub cleared_early: VarietyId,
     params: &'a Params
 }
 
@@ -140,6 +290,7 @@ pub struct BedPlanIterator<'a> {
     week: usize,
     planted_variety: VarietyId,
     planted_age: usize,
+    predecessor: VarietyId,
 }
 
 impl<'a> BedPlanIterator<'a> {
@@ -149,10 +300,11 @@ impl<'a> BedPlanIterator<'a> {
         match planting_from_prior_year {
             None =>
                 BedPlanIterator {
-                    bed_plan: bed_plan,
+                    bed_plan,
                     week: 0,
                     planted_variety: 0,
                     planted_age: 0,
+                    predecessor: 0,
                 },
             Some(prior_year_last_planting_week) => {
                 let planted_variety = bed_plan.get_variety_previous_year(prior_year_last_planting_week);
@@ -161,17 +313,19 @@ impl<'a> BedPlanIterator<'a> {
 
                 if is_alive {
                     BedPlanIterator {
-                        bed_plan: bed_plan,
+                        bed_plan,
                         week: 0,
-                        planted_variety: planted_variety,
-                        planted_age: SEASON_LENGTH - prior_year_last_planting_week
+                        planted_variety,
+                        planted_age: SEASON_LENGTH - prior_year_last_planting_week,
+                            predecessor: 0,
                     }
                 } else {
                     BedPlanIterator {
-                        bed_plan: bed_plan,
+                        bed_plan,
                         week: 0,
                         planted_variety: 0,
                         planted_age: 0,
+                            predecessor: planted_variety,
                     }
                 }
             }
@@ -179,6 +333,49 @@ impl<'a> BedPlanIterator<'a> {
     }
 }
 
+impl BedPlanIterator<'_> {
+    // Start or end crops according to the plan and to what was logged as actually happening
+    fn apply_week(&mut self, week: usize) {
+        let planned_variety = self.bed_plan.get_variety(week);
+        match self.bed_plan.get_actual(week) {
+            Some(Actual::Planted(variety)) => self.plant(variety),
+            Some(Actual::Failed) => self.plant(0),
+            Some(Actual::Removed) => self.plant(planned_variety),
+            None => {
+                // A planting that was logged in another week doesn't go ahead as planned
+                if planned_variety != 0 && !self.bed_plan.is_moved(week) {
+                    self.plant(planned_variety);
+                }
+            }
+        }
+    }
+
+    // Whether the growing crop must come out now to leave the bed empty for long
+    // enough before a planned planting. Only weeks of the season are checked, so a planting
+    // in its first weeks can follow a crop carried over from last year with less turnaround
+    fn is_turnaround(&self, week: usize) -> bool {
+        for weeks_ahead in 1..=self.bed_plan.max_turnaround {
+            let planting_week = week + weeks_ahead;
+            if planting_week >= SEASON_LENGTH || self.bed_plan.get_actual(planting_week).is_some() {
+                continue;
+            }
+            let planned_variety = self.bed_plan.get_variety(planting_week);
+            if planned_variety != 0 && !self.bed_plan.is_moved(planting_week) && self.bed_plan.get_turnaround(planned_variety) >= weeks_ahead {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn plant(&mut self, variety: VarietyId) {
+        if self.planted_variety != 0 {
+            self.predecessor = self.planted_variety;
+        }
+        self.planted_variety = variety;
+        self.planted_age = 0;
+    }
+}
+
 impl<'a> Iterator for BedPlanIterator<'a> {
     type Item = BedWeek<'a>;
 
@@ -187,14 +384,18 @@ impl<'a> Iterator for BedPlanIterator<'a> {
         match self.week {
             TWO_SEASONS => None,
             _ => {
-                if self.week < SEASON_LENGTH && self.bed_plan.get_variety(self.week) != 0 {
-                    self.planted_variety = self.bed_plan.get_variety(self.week);
-                    self.planted_age = 0;
+                if self.week < SEASON_LENGTH {
+                    self.apply_week(self.week);
                 }
 
                 if self.planted_variety != 0 && self.planted_age >= self.bed_plan.params.varieties[self.planted_variety].get_longevity() {
-                    self.planted_variety = 0;
-                    self.planted_age = 0;
+                    self.plant(0);
+                }
+
+                let mut cleared_early = 0;
+                if self.planted_variety != 0 && self.week < SEASON_LENGTH && self.is_turnaround(self.week) {
+                    cleared_early = self.planted_variety;
+                    self.plant(0);
                 }
 
                 let mut result = BedWeek{
@@ -202,15 +403,18 @@ impl<'a> Iterator for BedPlanIterator<'a> {
                     crop: self.planted_variety,
                     crop_age: self.planted_age,
                     harvestable_units: 0,
+                    predecessor: self.predecessor,
+                    cleared_early,
                     params: self.bed_plan.params
                 };
 
                 if self.planted_variety != 0 {
-                    result.harvestable_units = self
+                    let units = self
                         .bed_plan
                         .params
                         .varieties[self.planted_variety]
                         .harvest_schedule[self.planted_age];
+                    result.harvestable_units = (units as f64 * self.bed_plan.yield_scale).round() as i32;
                 }
 
                 self.week += 1;
@@ -222,3 +426,170 @@ impl<'a> Iterator for BedPlanIterator<'a> {
     }
 }
 
+#[cfg(test)]
+#[test]
+fn iterator_applies_actuals() {
+    use crate::events::{Event, EventKind, Actuals};
+
+    let (mut params, mut plan) = crate::params::test_params_and_plan();
+    params.varieties[1].harvest_schedule = vec![0, 0, 50, 50, 50, 50];
+    let event = |kind, week, variety: Option<&str>| Event{ kind, bed: "bed-0".to_string(), week, variety: variety.map(|v| v.to_string()), units: None };
+    params.actuals = Actuals::from_events(&[
+        event(EventKind::Failed, 12, None),
+        event(EventKind::Planted, 18, Some("var-1")),
+        event(EventKind::Removed, 32, None),
+    ], &params);
+
+    plan.get_data_mut()[20] = 1;
+    plan.get_data_mut()[30] = 1;
+    let bed_plan = BedPlan::new(0, &plan, &params);
+    let crops = bed_plan.iter().take(SEASON_LENGTH).map(|w| w.crop).collect::<Vec<_>>();
+
+    // the crop planted in week 10 failed in week 12
+    assert_eq!(crops[10..13], [1, 1, 0]);
+    // the planting in week 20 was brought forward to week 18 rather than repeated
+    let ages = bed_plan.iter().take(SEASON_LENGTH).map(|w| w.crop_age).collect::<Vec<_>>();
+    assert_eq!(ages[18..22], [0, 1, 2, 3]);
+    // the crop planted in week 30 was taken out in week 32
+    assert_eq!(crops[30..34], [1, 1, 0, 0]);
+}
+
+#[cfg(test)]
+#[test]
+fn iterator_moves_only_the_nearest_planting() {
+    use crate::events::{Event, EventKind, Actuals};
+
+    let (mut params, _) = crate::params::test_params_and_plan();
+    params.varieties[1].harvest_schedule = vec![0, 0, 50, 50, 50, 50, 50, 50, 50, 50];
+    params.actuals = Actuals::from_events(&[
+        Event{ kind: EventKind::Planted, bed: "bed-0".to_string(), week: 18, variety: Some("var-1".to_string()), units: None },
+    ], &params);
+
+    let mut plan = Plan::new(1);
+    plan.get_data_mut()[20] = 1;
+    plan.get_data_mut()[24] = 1;
+    let bed_plan = BedPlan::new(0, &plan, &params);
+    let ages = bed_plan.iter().take(SEASON_LENGTH).map(|w| w.crop_age).collect::<Vec<_>>();
+
+    // the planting in week 20 was brought forward to week 18, the one in week 24 still goes ahead
+    assert_eq!(ages[18..22], [0, 1, 2, 3]);
+    assert_eq!(ages[24..26], [0, 1]);
+}
+
+#[cfg(test)]
+#[test]
+fn iterator_moves_late_plantings() {
+    use crate::events::{Event, EventKind, Actuals};
+
+    let (mut params, _) = crate::params::test_params_and_plan();
+    params.varieties[1].harvest_schedule = vec![0, 0, 50, 50, 50, 50, 50, 50, 50, 50];
+    params.varieties[1].instructions.insert("0".to_string(), "Plant <variety>".to_string());
+    params.actuals = Actuals::from_events(&[
+        Event{ kind: EventKind::Planted, bed: "bed-0".to_string(), week: 18, variety: Some("var-1".to_string()), units: None },
+    ], &params);
+
+    // the planting in week 16 went in late in week 18
+    let mut plan = Plan::new(1);
+    plan.get_data_mut()[16] = 1;
+    let bed_plan = BedPlan::new(0, &plan, &params);
+    let crops = bed_plan.iter().take(SEASON_LENGTH).map(|w| w.crop).collect::<Vec<_>>();
+    let ages = bed_plan.iter().take(SEASON_LENGTH).map(|w| w.crop_age).collect::<Vec<_>>();
+    assert_eq!(crops[16..18], [0, 0]);
+    assert_eq!(ages[18..20], [0, 1]);
+    let mut tasks = Tasks::new();
+    bed_plan.write_instructions(&mut tasks);
+    assert!(tasks.get(16).is_empty());
+    assert_eq!(tasks.get(18), &vec!["Plant var-1".to_string()]);
+    assert!(bed_plan.to_string().contains("16       var-1                    moved"));
+
+    // with plantings as near on either side, the earlier went in late and the later still goes ahead
+    plan.get_data_mut()[20] = 1;
+    let bed_plan = BedPlan::new(0, &plan, &params);
+    let crops = bed_plan.iter().take(SEASON_LENGTH).map(|w| w.crop).collect::<Vec<_>>();
+    let ages = bed_plan.iter().take(SEASON_LENGTH).map(|w| w.crop_age).collect::<Vec<_>>();
+    assert_eq!(crops[16..18], [0, 0]);
+    assert_eq!(ages[18..22], [0, 1, 0, 1]);
+}
+
+#[cfg(test)]
+#[test]
+fn iterator_scales_yields_by_area() {
+    let mut params = Params::dummy(
+        vec![
+            Bed::dummy("bed-0"),
+            Bed{ area: Some(10.0), ..Bed::dummy("bed-1") },
+        ],
+        vec![ Variety::empty(), Variety::dummy("var-1", vec![]) ]
+    );
+    params.varieties[1].harvest_schedule = vec![0, 0, 50, 50];
+    let mut plan = Plan::new(2);
+    plan.get_data_mut()[10] = 1;
+    plan.get_data_mut()[SEASON_LENGTH+10] = 1;
+
+    let units = |bed| BedPlan::new(bed, &plan, &params).iter().map(|w| w.harvestable_units).sum::<i32>();
+    assert_eq!(units(0), 100);
+    // a third of the standard 30 square metres
+    assert_eq!(units(1), 34);
+}
+
+#[cfg(test)]
+#[test]
+fn iterator_leaves_turnaround() {
+    let mut params = Params::dummy(
+        vec![ Bed{ properties: vec!["polytunnel".to_string()], ..Bed::dummy("bed-0") } ],
+        vec![ Variety::empty(), Variety::dummy("var-1", vec![]), Variety::dummy("var-2", vec![]) ]
+    );
+    params.varieties[1].harvest_schedule = vec![0, 0, 50, 50, 50, 50, 50, 50, 50, 50];
+    params.varieties[2].harvest_schedule = vec![0, 0, 50, 50];
+    params.varieties[2].turnaround = 1;
+    params.turnaround.properties.insert("polytunnel".to_string(), 2);
+
+    let mut plan = Plan::new(1);
+    plan.get_data_mut()[10] = 1;
+    plan.get_data_mut()[15] = 2;
+    let bed_plan = BedPlan::new(0, &plan, &params);
+    let crops = bed_plan.iter().take(SEASON_LENGTH).map(|w| w.crop).collect::<Vec<_>>();
+    assert_eq!(crops[12..16], [1, 0, 0, 2]);
+
+    let mut tasks = Tasks::new();
+    bed_plan.write_instructions(&mut tasks);
+    assert_eq!(tasks.get(13), &vec!["Clear bed bed-0 of var-1 early, before its harvests are over".to_string()]);
+    assert_eq!(tasks.get(14), &vec!["Prepare bed bed-0 for var-2".to_string()]);
+    assert_eq!(tasks.get(19), &vec!["Clear bed bed-0 of var-2".to_string()]);
+    assert!(bed_plan.to_string().contains("13       var-1                    cleared early"));
+
+    // a planting in the first week isn't prepared for at the end of the same season
+    plan.get_data_mut()[0] = 2;
+    let bed_plan = BedPlan::new(0, &plan, &params);
+    let mut tasks = Tasks::new();
+    bed_plan.write_instructions(&mut tasks);
+    assert_eq!(tasks.get(4), &vec!["Clear bed bed-0 of var-2".to_string()]);
+    assert!(tasks.get(51).is_empty());
+}
+
+#[cfg(test)]
+#[test]
+fn cover_crop_is_incorporated() {
+    let mut params = Params::dummy(
+        vec![ Bed{ area: Some(15.0), ..Bed::dummy("bed-0") } ],
+        vec![ Variety::empty(), Variety::dummy("clover", vec![]), Variety::dummy("var-2", vec![]) ]
+    );
+    params.varieties[1].harvest_schedule = vec![0; 8];
+    params.varieties[1].soil_benefit = 10;
+    params.varieties[1].instructions.insert("incorporate".to_string(), "Dig <variety> into bed <bed>".to_string());
+    params.varieties[2].harvest_schedule = vec![0, 0, 50, 50];
+    assert!(params.varieties[1].is_cover_crop());
+
+    let mut plan = Plan::new(1);
+    plan.get_data_mut()[10] = 1;
+    plan.get_data_mut()[15] = 2;
+    plan.get_data_mut()[30] = 1;
+    let bed_plan = BedPlan::new(0, &plan, &params);
+    // 5 weeks, then 8 weeks, in a bed of half the standard size
+    assert_eq!(bed_plan.soil_benefit(), 65.0);
+
+    let mut tasks = Tasks::new();
+    bed_plan.write_instructions(&mut tasks);
+    assert_eq!(tasks.get(15), &vec!["Dig clover into bed bed-0".to_string()]);
+    assert_eq!(tasks.get(38), &vec!["Dig clover into bed bed-0".to_string()]);
+}
@@ -0,0 +1,173 @@
+use crate::params::Params;
+use crate::common::*;
+use crate::constant::{SEASON_LENGTH, VarietyId, HarvestableUnits};
+use crate::variety::parse_run_lengths;
+use std::error::Error;
+use json::JsonValue;
+use simple_error::*;
+
+// What a channel pays for a variety and how much of it the channel takes each week
+#[derive(Clone, Debug, PartialEq)]
+pub struct Offer {
+    // Price per unit, the variety's own price in the week if not given
+    pub price: Option<i32>,
+    // Units taken in each week, falling back on the variety's own demand where
+    // empty or shorter than a season, see share_variety_demand
+    pub demand: Vec<HarvestableUnits>,
+}
+
+// A way of selling the harvest, such as a box scheme, a market stall or a restaurant
+// Loaded from the "channels" section of params.json. Without one, everything is sold through
+// a single default channel at the varieties' own prices
+#[derive(Clone, Debug)]
+pub struct Channel {
+    pub name: String,
+    // Channels with a lower priority are supplied first, whatever they pay
+    pub priority: i32,
+    // Indexed by variety, None where the channel doesn't take the variety
+    pub offers: Vec<Option<Offer>>,
+}
+
+impl Channel {
+    // A channel taking every variety at its own price, up to its demand
+    pub fn default_for(num_varieties: usize) -> Self {
+        Channel {
+            name: "Default".to_string(),
+            priority: 0,
+            offers: vec![Some(Offer{ price: None, demand: vec![] }); num_varieties],
+        }
+    }
+
+    // Parse a channel, resolving variety names against the already loaded varieties
+    pub fn try_parse(value: &JsonValue, params: &Params) -> Result<Self, Box<dyn Error>> {
+        let value_obj = as_object(value)?;
+        let name = as_string(&value_obj["name"])?;
+        let priority = match value_obj.get("priority") {
+            Some(priority) => as_int(priority)?,
+            None => 0
+        };
+
+        let mut offers = vec![None; params.varieties.len()];
+        for (variety_name, offer_json) in as_object(&value_obj["varieties"])?.iter() {
+            let variety = match params.get_variety(variety_name) {
+                Some(variety) if variety != 0 => variety,
+                _ => bail!("Invalid channel {}: unknown variety {}", name, variety_name)
+            };
+            let price = match &offer_json["price"] {
+                JsonValue::Null => None,
+                price_json => Some(as_int(price_json)?)
+            };
+            let demand = match &offer_json["demand"] {
+                JsonValue::Null => vec![],
+                demand_json => parse_run_lengths(&as_string(demand_json)?)?
+            };
+            if demand.len() > SEASON_LENGTH {
+                bail!("Invalid channel {}: demand for {} is longer than a season", name, variety_name);
+            }
+            offers[variety] = Some(Offer{ price, demand });
+        }
+
+        Ok(Channel {
+            name,
+            priority,
+            offers,
+        })
+    }
+
+    pub fn get_price(&self, variety: VarietyId, week: usize, params: &Params) -> i32 {
+        match &self.offers[variety] {
+            Some(Offer{ price: Some(price), .. }) => *price,
+            Some(_) => params.varieties[variety].get_price(week),
+            None => 0
+        }
+    }
+
+    pub fn get_demand(&self, variety: VarietyId, week: usize, params: &Params) -> HarvestableUnits {
+        match &self.offers[variety] {
+            Some(offer) => match offer.demand.get(week % SEASON_LENGTH) {
+                Some(&demand) => demand,
+                None => params.get_variety_demand(variety, week)
+            },
+            None => 0
+        }
+    }
+}
+
+// Share the demand of each variety in the weeks where channels take it without giving their
+// own demand among those channels, so that listing a variety in several channels doesn't
+// multiply its market. Each channel's share is added to its demand
+pub fn share_variety_demand(channels: &mut [Channel], params: &Params) {
+    for variety in 1..params.varieties.len() {
+        for week in 0..SEASON_LENGTH {
+            let sharing = (0..channels.len())
+                .filter(|&c| matches!(&channels[c].offers[variety], Some(offer) if offer.demand.len() <= week))
+                .collect::<Vec<_>>();
+            if sharing.is_empty() {
+                continue;
+            }
+
+            // Any units left over go to the channels listed first
+            let demand = params.get_variety_demand(variety, week);
+            let num_sharing = sharing.len() as HarvestableUnits;
+            for (i, &c) in sharing.iter().enumerate() {
+                let share = demand / num_sharing + if (i as HarvestableUnits) < demand % num_sharing { 1 } else { 0 };
+                let offer = channels[c].offers[variety].as_mut().unwrap();
+                offer.demand.resize(week, 0);
+                offer.demand.push(share);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn channel_from_json() {
+    let params = Params::dummy(
+        vec![],
+        vec![ crate::variety::Variety::empty(), crate::variety::Variety::dummy("var-1", vec![]), crate::variety::Variety::dummy("var-2", vec![]) ]
+    );
+    let js = json::parse(r#"
+{
+    "name": "Market",
+    "priority": 2,
+    "varieties": {
+        "var-1": { "price": 150, "demand": "0:10,30:42" },
+        "var-2": {}
+    }
+}"#).expect("test is wrong");
+    let channel = Channel::try_parse(&js, &params).expect("failed to parse");
+    assert_eq!(channel.priority, 2);
+    assert_eq!(channel.get_price(1, 0, &params), 150);
+    assert_eq!(channel.get_demand(1, 9, &params), 0);
+    assert_eq!(channel.get_demand(1, 10, &params), 30);
+    assert_eq!(channel.get_price(2, 0, &params), 100);
+    assert_eq!(channel.get_demand(2, 10, &params), params.num_baskets);
+    assert_eq!(channel.get_demand(0, 10, &params), 0);
+}
+
+#[cfg(test)]
+#[test]
+fn channels_share_variety_demand() {
+    let mut params = Params::dummy(
+        vec![],
+        vec![ crate::variety::Variety::empty(), crate::variety::Variety::dummy("var-1", vec![]), crate::variety::Variety::dummy("var-2", vec![]) ]
+    );
+    params.num_baskets = 25;
+    let js = json::parse(r#"[
+    { "name": "Market", "varieties": { "var-1": {}, "var-2": { "demand": "0:10,8:30" } } },
+    { "name": "Shop", "varieties": { "var-1": {}, "var-2": {} } }
+]"#).expect("test is wrong");
+    let mut channels = js.members().map(|j| Channel::try_parse(j, &params)).collect::<Result<Vec<_>, _>>().expect("failed to parse");
+    share_variety_demand(&mut channels, &params);
+    params.channels = channels;
+
+    assert_eq!(params.channels[0].get_demand(1, 10, &params), 13);
+    assert_eq!(params.channels[1].get_demand(1, 10, &params), 12);
+    assert_eq!(params.get_demand(1, 10), 25);
+
+    // Where the market gives its own demand for var-2 the shop has the variety's demand to itself
+    assert_eq!(params.channels[0].get_demand(2, 10, &params), 8);
+    assert_eq!(params.channels[1].get_demand(2, 10, &params), 25);
+    assert_eq!(params.channels[0].get_demand(2, 45, &params), 13);
+    assert_eq!(params.channels[1].get_demand(2, 45, &params), 12);
+}
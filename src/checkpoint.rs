@@ -0,0 +1,125 @@
+use crate::plan::Plan;
+use crate::common::*;
+use crate::constant::WeekId;
+use crate::solver_params::SolverParams;
+use std::error::Error;
+use std::convert::TryFrom;
+use json::{JsonValue, object};
+
+// The state of a single island of the solver
+#[derive(Clone, Debug)]
+pub struct IslandCheckpoint {
+    pub seed: u64,
+    pub word_pos: u128,
+    pub population: Vec<Plan>,
+}
+
+// A snapshot of a solver run, from which the run can be resumed
+// Stored in .harvest/checkpoint.json by the Repo
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    pub seed: u64,
+    pub generation: usize,
+    pub num_gens_without_improvement: usize,
+    pub best_fitness: i32,
+    pub islands: Vec<IslandCheckpoint>,
+    // Set when the run is re-planning the current plan from this week
+    pub from_week: Option<WeekId>,
+    // The settings of the run, which a resumed run must share
+    pub solver: SolverParams,
+    // Set when the run was interrupted and its best plan so far stored as this revision,
+    // which the resumed run replaces
+    pub revision: Option<usize>,
+}
+
+impl Checkpoint {
+    pub fn to_json(&self) -> JsonValue {
+        object!{
+            "seed" => self.seed,
+            "generation" => self.generation,
+            "num_gens_without_improvement" => self.num_gens_without_improvement,
+            "best_fitness" => self.best_fitness,
+            "islands" => self.islands.iter().map(|i| i.to_json()).collect::<Vec<_>>(),
+            "from_week" => match self.from_week { Some(w) => w.into(), None => json::Null },
+            "solver" => self.solver.to_json(),
+            "revision" => match self.revision { Some(r) => r.into(), None => json::Null },
+        }
+    }
+}
+
+impl IslandCheckpoint {
+    fn to_json(&self) -> JsonValue {
+        object!{
+            "seed" => self.seed,
+            // 128 bit numbers don't survive being stored as JSON numbers
+            "word_pos" => self.word_pos.to_string(),
+            "population" => self.population.iter().map(|p| p.to_json()).collect::<Vec<_>>(),
+        }
+    }
+}
+
+impl TryFrom<&JsonValue> for IslandCheckpoint {
+    type Error = Box<dyn Error>;
+
+    fn try_from(value: &JsonValue) -> Result<Self, Self::Error> {
+        let population_json_array = as_array(&value["population"])?;
+        Ok(IslandCheckpoint {
+            seed: as_u64(&value["seed"])?,
+            word_pos: as_string(&value["word_pos"])?.parse::<u128>()?,
+            population: population_json_array.iter().map(Plan::try_from).collect::<Result<Vec<_>, _>>()?,
+        })
+    }
+}
+
+impl TryFrom<&JsonValue> for Checkpoint {
+    type Error = Box<dyn Error>;
+
+    fn try_from(value: &JsonValue) -> Result<Self, Self::Error> {
+        let islands_json_array = as_array(&value["islands"])?;
+        Ok(Checkpoint {
+            seed: as_u64(&value["seed"])?,
+            generation: as_usize(&value["generation"])?,
+            num_gens_without_improvement: as_usize(&value["num_gens_without_improvement"])?,
+            best_fitness: as_int(&value["best_fitness"])?,
+            islands: islands_json_array.iter().map(IslandCheckpoint::try_from).collect::<Result<Vec<_>, _>>()?,
+            from_week: match value["from_week"].is_null() {
+                true => None,
+                false => Some(as_usize(&value["from_week"])?)
+            },
+            solver: SolverParams::try_from(&value["solver"])?,
+            revision: match value["revision"].is_null() {
+                true => None,
+                false => Some(as_usize(&value["revision"])?)
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn checkpoint_round_trip() {
+    let checkpoint = Checkpoint {
+        seed: 42,
+        generation: 150,
+        num_gens_without_improvement: 50,
+        best_fitness: -3,
+        islands: vec![ IslandCheckpoint {
+            seed: 42,
+            word_pos: u128::from(u64::MAX) + 7,
+            population: vec![ Plan::new(1), Plan::new(1) ]
+        } ],
+        from_week: Some(20),
+        solver: SolverParams{ max_time: Some(600), ..SolverParams::default() },
+        revision: None
+    };
+
+    let loaded = Checkpoint::try_from(&checkpoint.to_json()).expect("failed to parse");
+    assert_eq!(loaded.seed, 42);
+    assert_eq!(loaded.generation, 150);
+    assert_eq!(loaded.best_fitness, -3);
+    assert_eq!(loaded.islands[0].word_pos, u128::from(u64::MAX) + 7);
+    assert_eq!(loaded.islands[0].population.len(), 2);
+    assert_eq!(loaded.from_week, Some(20));
+    assert_eq!(loaded.solver, checkpoint.solver);
+    assert_eq!(loaded.revision, None);
+}
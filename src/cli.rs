@@ -16,24 +16,190 @@ pub struct ParamsPrint {
 }
 
 #[derive(Debug, StructOpt)]
-pub struct ParamsTasks {
-    #[structopt(short, long)]
+pub struct ParamsSolve {
+    #[structopt(short, long, help = "Seed for the random number generator, to reproduce an earlier plan")]
+    pub seed: Option<u64>,
+
+    #[structopt(short, long, conflicts_with = "seed", help = "Continue the last run from its checkpoint")]
+    pub resume: bool,
+
+    #[structopt(long, conflicts_with = "resume", help = "Start from the current plan rather than from scratch")]
+    pub from_current: bool,
+
+    #[structopt(flatten)]
+    pub solver: SolverOptions
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ParamsReplan {
+    #[structopt(short = "w", long, help = "First week to re-plan, earlier weeks are kept as they are")]
+    pub from_week: usize,
+
+    #[structopt(short, long, help = "Seed for the random number generator, to reproduce an earlier plan")]
+    pub seed: Option<u64>,
+
+    #[structopt(flatten)]
+    pub solver: SolverOptions
+}
+
+// Overrides for the solver settings in params.json
+#[derive(Debug, StructOpt)]
+pub struct SolverOptions {
+    #[structopt(long, help = "Number of individuals in the population")]
+    pub population_size: Option<usize>,
+
+    #[structopt(long, help = "Stop after this many generations without improvement")]
+    pub stagnation_limit: Option<usize>,
+
+    #[structopt(long, help = "Number of mutations applied to each mutated child")]
+    pub mutations: Option<usize>,
+
+    #[structopt(long, help = "Probability that a child is mutated")]
+    pub mutation_rate: Option<f64>,
+
+    #[structopt(long, help = "Number of best individuals carried unchanged into the next generation")]
+    pub elitism: Option<usize>,
+
+    #[structopt(long, help = "How strongly parent selection favours fitter individuals")]
+    pub selection_pressure: Option<f64>,
+
+    #[structopt(long, help = "Stop after this many generations")]
+    pub max_generations: Option<usize>,
+
+    #[structopt(long, help = "Stop after this many seconds")]
+    pub max_time: Option<u64>,
+
+    #[structopt(long, help = "Stop as soon as a plan with at least this much profit is found")]
+    pub target_profit: Option<f64>,
+
+    #[structopt(long, help = "Number of populations evolved side by side")]
+    pub islands: Option<usize>,
+
+    #[structopt(short, long, help = "Number of threads to use, defaults to one per CPU")]
+    pub threads: Option<usize>
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ParamsPin {
+    #[structopt(short, long, help = "Name of the bed")]
+    pub bed: std::string::String,
+
+    #[structopt(short, long, help = "Week of the planting, or the first week to keep the bed empty")]
+    pub week: usize,
+
+    #[structopt(short, long, help = "Variety to plant, leave out to keep the bed empty")]
+    pub variety: Option<std::string::String>,
+
+    #[structopt(short, long, conflicts_with = "variety", help = "Last week to keep the bed empty")]
+    pub until: Option<usize>,
+
+    #[structopt(short, long, conflicts_with_all = &["variety", "until"], help = "Remove the pins added for the bed in the week instead")]
+    pub remove: bool
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ParamsRisk {
+    #[structopt(short = "n", long, default_value = "1000", help = "Number of seasons to simulate")]
+    pub simulations: usize,
+
+    #[structopt(short, long, default_value = "0", help = "Seed for the random number generator")]
+    pub seed: u64
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ParamsLogBedWeek {
+    #[structopt(short, long, help = "Name of the bed")]
+    pub bed: std::string::String,
+
+    #[structopt(short, long, help = "Week in which it happened")]
     pub week: usize
 }
 
+#[derive(Debug, StructOpt)]
+pub struct ParamsLogPlanted {
+    #[structopt(short, long, help = "Name of the bed")]
+    pub bed: std::string::String,
+
+    #[structopt(short, long, help = "Week in which it was planted")]
+    pub week: usize,
+
+    #[structopt(short, long, help = "Variety that was planted")]
+    pub variety: std::string::String
+}
+
+#[derive(Debug, StructOpt)]
+pub struct ParamsLogHarvest {
+    #[structopt(short, long, help = "Name of the bed")]
+    pub bed: std::string::String,
+
+    #[structopt(short, long, help = "Week of the harvest")]
+    pub week: usize,
+
+    #[structopt(short, long, help = "Variety that was harvested")]
+    pub variety: std::string::String,
+
+    #[structopt(short, long, help = "Number of units harvested")]
+    pub units: i32
+}
+
+#[derive(Debug, StructOpt)]
+pub enum LogCmd {
+    #[structopt(name = "fail", about = "The crop in a bed failed")]
+    Fail(ParamsLogBedWeek),
+
+    #[structopt(name = "planted", about = "A variety was planted, whether or not as planned")]
+    Planted(ParamsLogPlanted),
+
+    #[structopt(name = "removed", about = "The crop in a bed was taken out early")]
+    Removed(ParamsLogBedWeek),
+
+    #[structopt(name = "harvest", about = "A quantity of a variety was harvested from a bed")]
+    Harvest(ParamsLogHarvest),
+}
+
+#[derive(Debug, StructOpt)]
+pub enum ReportCmd {
+    #[structopt(name = "harvests", about = "Compare the logged harvests with the forecast")]
+    Harvests,
+
+    #[structopt(name = "workload", about = "Chart the hours of work in each week against the labour available")]
+    Workload,
+
+    #[structopt(name = "nursery", about = "Show the trays in the nursery each week and what to sow")]
+    Nursery,
+}
+
 #[derive(Debug, StructOpt)]
 pub enum Cmd {
     #[structopt(name = "init", about = "Initialize a new harvest repository in the current directory")]
     Init(ParamsInit),
 
     #[structopt(name = "plan", about = "Design a crop plan based on the current parameter settings")]
-    Solve,
+    Solve(ParamsSolve),
+
+    #[structopt(name = "replan", about = "Re-design the rest of the current crop plan from a given week")]
+    Replan(ParamsReplan),
 
     #[structopt(name = "reset", about = "Drop the current crop plan")]
     Reset,
 
     #[structopt(name = "print", about = "Print the current crop plan to the console")]
     Print(ParamsPrint),
+
+    #[structopt(name = "log", about = "Record what actually happened in a bed")]
+    Log(LogCmd),
+
+    #[structopt(name = "report", about = "Print a report on the current crop plan")]
+    Report(ReportCmd),
+
+    #[structopt(name = "pin", about = "Fix a planting, or keep a bed empty, in future plans")]
+    Pin(ParamsPin),
+
+    #[structopt(name = "risk", about = "Simulate the current crop plan with uncertain yields")]
+    Risk(ParamsRisk),
+
+    #[structopt(name = "check", about = "List the plantings in the current crop plan that break the rotation or succession rules")]
+    Check,
 }
 
 #[derive(StructOpt, Debug)]
@@ -41,8 +207,5 @@ pub enum Cmd {
 pub struct Cli {
     #[structopt(subcommand)]
     pub command: Cmd,
-
-    #[structopt(short, long, default_value = "params.json", parse(from_os_str))]
-    params: std::path::PathBuf,
 }
 
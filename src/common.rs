@@ -1,6 +1,5 @@
 use json::JsonValue;
 use std::fs;
-use std;
 
 pub fn as_object(thing: &JsonValue) -> Result<&json::object::Object, &'static str> {
     match thing {
@@ -38,6 +37,20 @@ pub fn as_usize(thing: &JsonValue) -> Result<usize, &'static str> {
     }
 }
 
+pub fn as_u64(thing: &JsonValue) -> Result<u64, &'static str> {
+    match thing {
+        JsonValue::Number(n) => Ok((*n).into()),
+        _ => Err("Expected JSON number")
+    }
+}
+
+pub fn as_f64(thing: &JsonValue) -> Result<f64, &'static str> {
+    match thing {
+        JsonValue::Number(n) => Ok((*n).into()),
+        _ => Err("Expected JSON number")
+    }
+}
+
 pub fn sha256_digest(path: &std::path::PathBuf) -> Result<std::string::String, std::io::Error> {
     let json = fs::read_to_string(path)?;
     let mut hasher = sha1::Sha1::new();
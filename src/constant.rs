@@ -6,5 +6,9 @@ pub type WeekRange = usize;
 pub type HarvestableUnits = i32;
 
 pub const SEASON_LENGTH: WeekRange = 52;
-pub const POPULATION_SIZE: usize = 25;
+pub const DEFAULT_POPULATION_SIZE: usize = 25;
+pub const DEFAULT_STAGNATION_LIMIT: usize = 1000;
+pub const DEFAULT_ISLANDS: usize = 4;
+pub const DEFAULT_MIGRATION_INTERVAL: usize = 50;
+pub const DEFAULT_CHECKPOINT_INTERVAL: usize = 500;
 
@@ -3,10 +3,21 @@ use crate::constant::SEASON_LENGTH;
 use crate::tasks::Tasks;
 use crate::params::Params;
 use crate::bed_plan::BedPlan;
+use crate::nursery::{Nursery, Sowing};
+use crate::risk::Outcome;
+
+// The outcome of selling a harvest plan through the channels
+struct Sales {
+    revenue_by_channel: Vec<i32>,
+    // Units of each variety in store at the start of each week
+    stock: Vec<Vec<i32>>,
+}
 
 pub struct Evaluator<'a> {
     params: &'a Params,
-    plan: &'a Plan
+    plan: &'a Plan,
+    // Simulated yields, the harvest_schedules are taken as certain without one
+    outcome: Option<&'a Outcome>
 }
 
 impl<'a> Evaluator<'a> {
@@ -14,43 +25,83 @@ impl<'a> Evaluator<'a> {
         params: &'a Params, 
         plan: &'a Plan) -> Self {
         Evaluator {
-            params: params,
-            plan: plan
+            params,
+            plan,
+            outcome: None
+        }
+    }
+
+    // Evaluate the plan with the yields of a simulated outcome
+    pub fn with_outcome(params: &'a Params, plan: &'a Plan, outcome: &'a Outcome) -> Self {
+        Evaluator {
+            params,
+            plan,
+            outcome: Some(outcome)
         }
     }
 
     pub fn get_harvest_plan(&self) -> Vec<Vec<i32>> {
+        self.get_harvest_plan_and_cost(SEASON_LENGTH*2).0
+    }
+
+    // The harvest plan along with the cost of production over the given number of weeks,
+    // worked out together so that the bed plans are only iterated once
+    fn get_harvest_plan_and_cost(&self, season_length: usize) -> (Vec<Vec<i32>>, i32) {
         let mut harvest_plan = vec![ vec![0i32; SEASON_LENGTH * 2]; self.params.varieties.len() ];
+        let mut cost = 0;
 
         for bed in 0..self.params.beds.len() {
             let bed_plan = self.get_bed_plan(bed);
             for bed_week in bed_plan.iter() {
-                if bed_week.harvestable_units != 0 {
-                    harvest_plan[bed_week.crop][bed_week.week] += bed_week.harvestable_units;
+                let harvestable_units = match self.outcome {
+                    Some(outcome) if bed_week.harvestable_units != 0 => {
+                        let planting_week = bed_week.week as i32 - bed_week.crop_age as i32;
+                        let factor = outcome.get_yield_factor(bed, planting_week, &self.params.varieties[bed_week.crop]);
+                        (bed_week.harvestable_units as f64 * factor).round() as i32
+                    },
+                    _ => bed_week.harvestable_units
+                };
+                if harvestable_units != 0 {
+                    harvest_plan[bed_week.crop][bed_week.week] += harvestable_units;
+                }
+                if bed_week.crop != 0 && bed_week.week < season_length {
+                    let costs = &self.params.varieties[bed_week.crop].costs;
+                    if bed_week.crop_age == 0 {
+                        cost += costs.per_planting();
+                    }
+                    cost += costs.bed_week + costs.harvest_unit * harvestable_units;
                 }
             }
         }
 
-        harvest_plan
+        (harvest_plan, cost)
+    }
+
+    // Units of each variety in store at the start of each week, see Variety::storage_life
+    pub fn get_stock_plan(&self) -> Vec<Vec<i32>> {
+        self.sell(&self.get_harvest_plan(), SEASON_LENGTH).stock
     }
 
     // Returns a number between 0 and 1 representing the extent to which each variety that could
-    // possibly be harvested in each week can be harvested with enough quantity to fill the market
+    // possibly be harvested in each week can be harvested, or taken from store, with enough
+    // quantity to fill the market
     pub fn get_market_saturation(&self) -> f32 {
 
         let harvest_plan = self.get_harvest_plan();
+        let stock_plan = self.sell(&harvest_plan, SEASON_LENGTH).stock;
 
         let mut potential = 0;
         let mut actual = 0;
 
         for variety in 0..self.params.varieties.len() {
              for week in 0..SEASON_LENGTH {
-                if self.params.varieties[variety].is_harvestable_in_week(week) {
+                let demand = self.params.get_demand(variety, week);
+                if self.params.varieties[variety].is_harvestable_in_week(week) && demand > 0 {
                     potential += 1;
-                    if harvest_plan[variety][week] >= self.params.num_baskets {
+                    if harvest_plan[variety][week] + stock_plan[variety][week] >= demand {
                         actual += 1;
                     }
-                }  
+                }
             }
         }
 
@@ -67,27 +118,195 @@ impl<'a> Evaluator<'a> {
 
     // The fitness function used by the evolutionary algorithm. Captures the value of
     // all crops harvested _or planted_ between jan1 and dec 31. This encourages plans
-    // that set up good overwintered crops for next year.
+    // that set up good overwintered crops for next year. Penalties for breaking the
+    // rotation and succession rules and for overloading the labour or the nursery are
+    // subtracted, bonuses for preferred successions and the benefit of cover crops added
+    // Large penalties saturate rather than overflow
     pub fn get_fitness(&self) -> i32 {
-        self._get_profit(SEASON_LENGTH*2)
+        (self.get_risk_adjusted_profit() + self.get_soil_benefit())
+            .saturating_sub(self.get_rotation_penalty())
+            .saturating_add(self.get_succession_score())
+            .saturating_sub(self.get_labour_penalty())
+            .saturating_sub(self.get_nursery_penalty())
+    }
+
+    fn get_rotation_penalty(&self) -> i32 {
+        match self.params.rotation.penalty {
+            Some(penalty) => penalty.saturating_mul(self.params.rotation.find_violations(self.plan, self.params).len() as i32),
+            None => 0
+        }
+    }
+
+    // The profit over two seasons, adjusted for the risk of the yields if the solver
+    // is asked to, see RiskObjective
+    fn get_risk_adjusted_profit(&self) -> i32 {
+        match &self.params.risk {
+            Some(risk) => {
+                let profits = risk.outcomes
+                    .iter()
+                    .map(|outcome| Evaluator::with_outcome(self.params, self.plan, outcome)._get_profit(SEASON_LENGTH*2))
+                    .collect::<Vec<_>>();
+                risk.adjust(&profits)
+            },
+            None => self._get_profit(SEASON_LENGTH*2)
+        }
+    }
+
+    fn get_labour_penalty(&self) -> i32 {
+        match &self.params.labour {
+            Some(labour) => (labour.get_overload(&self.get_workload()) * labour.penalty_per_hour as f64).round() as i32,
+            None => 0
+        }
+    }
+
+    // Minutes of work in each week of the season, as estimated by the varieties' instructions
+    pub fn get_workload(&self) -> Vec<f64> {
+        let mut workload = vec![0.0; SEASON_LENGTH];
+        for bed in 0..self.params.beds.len() {
+            self.get_bed_plan(bed).add_workload(&mut workload);
+        }
+        workload
+    }
+
+    fn get_nursery_penalty(&self) -> i32 {
+        match &self.params.nursery {
+            Some(nursery) => (nursery.get_overload(&self.get_nursery_occupancy(nursery)) * nursery.penalty_per_tray as f64).round() as i32,
+            None => 0
+        }
+    }
+
+    // The plantings raised in the nursery, ordered by bed and planting week
+    pub fn get_sowings(&self) -> Vec<Sowing> {
+        let mut sowings = vec![];
+        for bed in 0..self.params.beds.len() {
+            self.get_bed_plan(bed).add_sowings(&mut sowings);
+        }
+        sowings
+    }
+
+    // Trays in the nursery in each week of the season, indexed by the nursery's tray types
+    pub fn get_nursery_occupancy(&self, nursery: &Nursery) -> Vec<Vec<f64>> {
+        let mut occupancy = vec![vec![0.0; SEASON_LENGTH]; nursery.trays.len()];
+        for sowing in self.get_sowings() {
+            let propagation = self.params.varieties[sowing.variety].propagation.as_ref().unwrap();
+            if let Some(tray) = nursery.get_tray(&propagation.tray) {
+                for week in sowing.get_weeks() {
+                    occupancy[tray][week] += sowing.trays;
+                }
+            }
+        }
+        occupancy
+    }
+
+    fn get_succession_score(&self) -> i32 {
+        let succession = &self.params.succession;
+        let mut score = 0;
+        if let Some(penalty) = succession.penalty {
+            score -= penalty.saturating_mul(succession.find_avoided(self.plan, self.params).len() as i32);
+        }
+        if succession.bonus != 0 {
+            score = succession.bonus
+                .saturating_mul(succession.find_preferred(self.plan, self.params).len() as i32)
+                .saturating_add(score);
+        }
+        score
+    }
+
+    // The value of the harvest sold over one season, before costs
+    pub fn get_revenue(&self) -> i32 {
+        self._get_revenue(&self.get_harvest_plan(), SEASON_LENGTH)
+    }
+
+    // The cost of producing the crops over one season, see Costs
+    pub fn get_cost(&self) -> i32 {
+        self.get_harvest_plan_and_cost(SEASON_LENGTH).1
     }
 
     fn _get_profit(&self, season_length: usize) -> i32 {
-        // TODO model cost of production better
-        let cost: i32 = self.plan.get_num_plantings();
-        let mut profit = -cost;
+        let (harvest_plan, cost) = self.get_harvest_plan_and_cost(season_length);
+        self._get_revenue(&harvest_plan, season_length) - cost
+    }
+
+    fn _get_revenue(&self, harvest_plan: &[Vec<i32>], season_length: usize) -> i32 {
+        self.sell(harvest_plan, season_length).revenue_by_channel.iter().sum()
+    }
+
+    // The revenue from each sales channel over one season
+    pub fn get_revenue_by_channel(&self) -> Vec<i32> {
+        self.sell(&self.get_harvest_plan(), SEASON_LENGTH).revenue_by_channel
+    }
+
+    // Sell the harvest of each week through the channels, along with any stored harvest of
+    // earlier weeks. Channels are supplied in order of priority and then of price in the week,
+    // so that within a priority the harvest goes where it makes the most. The oldest stock
+    // is sold first, and whatever is left of a storable harvest goes into store
+    fn sell(&self, harvest_plan: &[Vec<i32>], season_length: usize) -> Sales {
+        let channels = &self.params.channels;
+        let mut revenue_by_channel = vec![0; channels.len()];
+        let mut stock = vec![ vec![0i32; season_length]; self.params.varieties.len() ];
 
-        let harvest_plan = self.get_harvest_plan();
         for variety in 0..self.params.varieties.len() {
+            let variety_def = &self.params.varieties[variety];
+            let mut order = (0..channels.len())
+                .filter(|&c| channels[c].offers[variety].is_some())
+                .collect::<Vec<_>>();
+            // Batches of stored harvest as (weeks in store, units), oldest first. Units are kept
+            // in fractions so that spoilage takes its share of small batches too, only whole units are sold
+            let mut store: Vec<(usize, f64)> = vec![];
+
             for week in 0..season_length {
-                let harvestable_units = harvest_plan[variety][week];
-                let sellable_units = std::cmp::min(self.params.num_baskets, harvestable_units);
-                let val = sellable_units * self.params.varieties[variety].value_per_unit;
-                profit += val; 
+                let harvested_units = harvest_plan[variety][week];
+                let stored_units = store.iter().map(|batch| batch.1).sum::<f64>() as i32;
+                stock[variety][week] = stored_units;
+                if harvested_units + stored_units == 0 {
+                    continue;
+                }
+
+                let mut unsold_units = harvested_units + stored_units;
+                order.sort_by_key(|&c| (channels[c].priority, -channels[c].get_price(variety, week, self.params)));
+                for &channel in order.iter() {
+                    if unsold_units == 0 {
+                        break;
+                    }
+                    let demand = channels[channel].get_demand(variety, week, self.params);
+                    let sold_units = std::cmp::min(demand, unsold_units);
+                    revenue_by_channel[channel] += sold_units * channels[channel].get_price(variety, week, self.params);
+                    unsold_units -= sold_units;
+                }
+
+                if variety_def.storage_life > 0 {
+                    let mut units_to_take = (harvested_units + stored_units - unsold_units) as f64;
+                    for batch in store.iter_mut() {
+                        let taken_units = batch.1.min(units_to_take);
+                        batch.1 -= taken_units;
+                        units_to_take -= taken_units;
+                    }
+                    store.push((0, harvested_units as f64 - units_to_take));
+                    for batch in store.iter_mut() {
+                        batch.0 += 1;
+                        batch.1 *= 1.0 - variety_def.spoilage;
+                    }
+                    store.retain(|batch| batch.0 <= variety_def.storage_life && batch.1 > 0.0);
+                }
             }
         }
 
-        profit
+        Sales {
+            revenue_by_channel,
+            stock
+        }
+    }
+
+    // The value to the soil of the cover crops in the plan, see Variety::soil_benefit
+    pub fn get_soil_benefit(&self) -> i32 {
+        if self.params.varieties.iter().all(|v| v.soil_benefit == 0) {
+            return 0;
+        }
+        let mut soil_benefit = 0.0;
+        for bed in 0..self.params.beds.len() {
+            soil_benefit += self.get_bed_plan(bed).soil_benefit();
+        }
+        soil_benefit.round() as i32
     }
 
     pub fn get_bed_utilization(&self) -> f32 {
@@ -110,6 +329,165 @@ impl<'a> Evaluator<'a> {
     }
 
     fn get_bed_plan(&'a self, bed: usize) -> BedPlan<'a> {
-        BedPlan::new(bed, &self.plan, self.params)
+        BedPlan::new(bed, self.plan, self.params)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+#[test]
+fn profit_subtracts_costs() {
+    let (mut params, plan) = crate::params::test_params_and_plan();
+    params.varieties[1].costs = crate::variety::Costs{ seed: 30, planting: 20, harvest_unit: 2, bed_week: 10 };
+
+    let evaluator = Evaluator::new(&params, &plan);
+
+    assert_eq!(evaluator.get_revenue(), 100 * 100);
+    assert_eq!(evaluator.get_cost(), 30 + 20 + 2 * 100 + 10 * 4);
+    assert_eq!(evaluator.get_profit(), evaluator.get_revenue() - evaluator.get_cost());
+}
+
+#[cfg(test)]
+#[test]
+fn revenue_is_capped_by_demand() {
+    let (mut params, mut plan) = crate::params::test_params_and_plan();
+    params.varieties[1].demand = vec![100; 14];
+    params.varieties[1].demand[13] = 20;
+
+    plan.get_data_mut()[20] = 1;
+    let evaluator = Evaluator::new(&params, &plan);
+
+    // week 12 sells 50, week 13 only 20, weeks 22 and 23 fall back on num_baskets
+    assert_eq!(evaluator.get_revenue(), (50 + 20 + 50 + 50) * 100);
+}
+
+#[cfg(test)]
+#[test]
+fn harvest_is_allocated_to_channels() {
+    use crate::channel::{Channel, Offer};
+
+    let (mut params, plan) = crate::params::test_params_and_plan();
+    let channel = |name: &str, priority, price, demand| Channel {
+        name: name.to_string(),
+        priority,
+        offers: vec![ None, Some(Offer{ price: Some(price), demand: vec![demand; SEASON_LENGTH] }) ]
+    };
+    params.channels = vec![ channel("cheap", 0, 100, 40), channel("dear", 0, 300, 30), channel("committed", -1, 50, 5) ];
+
+    let evaluator = Evaluator::new(&params, &plan);
+
+    // each week the committed channel takes 5 first, then the dear one 30 and the cheap one the rest
+    assert_eq!(evaluator.get_revenue_by_channel(), vec![ 2 * 15 * 100, 2 * 30 * 300, 2 * 5 * 50 ]);
+    assert_eq!(params.get_demand(1, 12), 75);
+}
+
+#[cfg(test)]
+#[test]
+fn surplus_is_stored() {
+    let (mut params, plan) = crate::params::test_params_and_plan();
+    params.varieties[1].harvest_schedule = vec![0, 0, 200];
+    params.varieties[1].demand = vec![50; SEASON_LENGTH];
+    params.varieties[1].storage_life = 2;
+    params.varieties[1].spoilage = 0.1;
+
+    let evaluator = Evaluator::new(&params, &plan);
+
+    // 150 go into store in week 12 and 135 are left after spoilage, 50 are sold each week
+    // and what's left after 2 weeks in store is thrown away
+    let stock = evaluator.get_stock_plan();
+    assert_eq!(stock[1][12..16], [0, 135, 76, 0]);
+    assert_eq!(evaluator.get_revenue(), 50 * 3 * 100);
+
+    // A small batch loses a tenth a week rather than a unit
+    params.varieties[1].harvest_schedule = vec![0, 0, 55];
+    params.varieties[1].demand = vec![0; SEASON_LENGTH];
+    params.varieties[1].demand[12] = 50;
+    params.varieties[1].storage_life = 4;
+    let stock = Evaluator::new(&params, &plan).get_stock_plan();
+    assert_eq!(stock[1][12..18], [0, 4, 4, 3, 3, 0]);
+}
+
+#[cfg(test)]
+#[test]
+fn revenue_follows_price_schedule() {
+    let (mut params, plan) = crate::params::test_params_and_plan();
+    params.varieties[1].price_schedule = vec![300; 13];
+
+    let evaluator = Evaluator::new(&params, &plan);
+
+    // week 12 is early season, week 13 falls back on value_per_unit
+    assert_eq!(evaluator.get_revenue(), 50 * 300 + 50 * 100);
+}
+
+#[cfg(test)]
+#[test]
+fn overloaded_labour_is_penalised() {
+    use crate::labour::{Labour, LabourCapacity};
+
+    let (mut params, plan) = crate::params::test_params_and_plan();
+    params.varieties[1].labour.insert("-2".to_string(), Labour::PerBed(60.0));
+    params.varieties[1].labour.insert("0".to_string(), Labour::PerBed(120.0));
+    params.varieties[1].labour.insert("harvest".to_string(), Labour::PerUnit(1.2));
+
+    let fitness = Evaluator::new(&params, &plan).get_fitness();
+    let workload = Evaluator::new(&params, &plan).get_workload();
+    assert_eq!(workload[8], 60.0);
+    assert_eq!(workload[10], 120.0);
+    assert_eq!(workload[12], 60.0);
+
+    params.labour = Some(LabourCapacity{ hours: vec![1.5; SEASON_LENGTH], penalty_per_hour: 100 });
+    assert_eq!(Evaluator::new(&params, &plan).get_fitness(), fitness - 50);
+}
+
+#[cfg(test)]
+#[test]
+fn nursery_overload_is_penalised() {
+    use crate::nursery::Propagation;
+
+    let mut params = Params::dummy(
+        vec![
+            crate::bed::Bed::dummy("bed-0"),
+            crate::bed::Bed{ area: Some(15.0), ..crate::bed::Bed::dummy("bed-1") },
+        ],
+        vec![ crate::variety::Variety::empty(), crate::variety::Variety::dummy("var-1", vec![]) ]
+    );
+    params.varieties[1].harvest_schedule = vec![0, 0, 50, 50];
+    params.varieties[1].instructions.insert("-3".to_string(), "Sow <variety>".to_string());
+    params.varieties[1].propagation = Some(Propagation{ tray: "144".to_string(), trays_per_bed: 4.0 });
+
+    let mut plan = Plan::new(2);
+    plan.get_data_mut()[10] = 1;
+    plan.get_data_mut()[SEASON_LENGTH+11] = 1;
+    let fitness = Evaluator::new(&params, &plan).get_fitness();
+
+    let nursery = Nursery{ trays: vec!["144".to_string()], capacity: vec![5.0], penalty_per_tray: 100 };
+    let occupancy = Evaluator::new(&params, &plan).get_nursery_occupancy(&nursery);
+    assert_eq!(occupancy[0][6..12], [0.0, 4.0, 6.0, 6.0, 2.0, 0.0]);
+
+    params.nursery = Some(nursery);
+    assert_eq!(Evaluator::new(&params, &plan).get_fitness(), fitness - 200);
+}
+
+#[cfg(test)]
+#[test]
+fn large_penalties_saturate() {
+    let (mut params, mut plan) = crate::params::test_params_and_plan();
+    params.varieties[1].family = Some("Brassica".to_string());
+    params.rotation.gaps.insert("Brassica".to_string(), 40);
+
+    plan.get_data_mut()[20] = 1;
+    plan.get_data_mut()[30] = 1;
+    let fitness = Evaluator::new(&params, &plan).get_fitness();
+
+    params.rotation.penalty = Some(i32::MAX);
+    assert_eq!(Evaluator::new(&params, &plan).get_fitness(), fitness.saturating_sub(i32::MAX));
+
+    params.rotation.penalty = Some(0);
+    params.varieties[1].avoid_after = vec!["var-1".to_string()];
+    params.succession.penalty = Some(i32::MAX);
+    assert_eq!(Evaluator::new(&params, &plan).get_fitness(), fitness.saturating_sub(i32::MAX));
+
+    params.varieties[1].avoid_after = vec![];
+    params.varieties[1].prefer_after = vec!["var-1".to_string()];
+    params.succession.bonus = i32::MAX;
+    assert_eq!(Evaluator::new(&params, &plan).get_fitness(), fitness.saturating_add(i32::MAX));
+}
@@ -0,0 +1,204 @@
+use crate::params::Params;
+use crate::common::*;
+use crate::constant::{SEASON_LENGTH, VarietyId, WeekId};
+use std::error::Error;
+use std::convert::TryFrom;
+use json::{JsonValue, object};
+use simple_error::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EventKind {
+    // The crop growing in the week failed, nothing more is harvested from it
+    Failed,
+    // A variety was actually planted in the week, whatever the plan says
+    Planted,
+    // The crop growing in the week was taken out early
+    Removed,
+    // Some units of a variety were harvested from the bed in the week
+    Harvested,
+}
+
+impl EventKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::Failed => "failed",
+            EventKind::Planted => "planted",
+            EventKind::Removed => "removed",
+            EventKind::Harvested => "harvested",
+        }
+    }
+}
+
+// Something that actually happened in a bed, as logged with 'harvest log' and stored by the Repo
+// Beds and varieties are stored by name so that events survive changes to params.json
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub kind: EventKind,
+    pub bed: String,
+    pub week: WeekId,
+    pub variety: Option<String>,
+    pub units: Option<i32>,
+}
+
+impl Event {
+    // Check that the event refers to a known bed, week and variety
+    pub fn validate(&self, params: &Params) -> Result<(), Box<dyn Error>> {
+        if params.get_bed(&self.bed).is_none() {
+            bail!("Invalid event: unknown bed {}", self.bed);
+        }
+        if self.week >= SEASON_LENGTH {
+            bail!("Invalid event: bad week {}", self.week);
+        }
+        if self.kind == EventKind::Planted || self.kind == EventKind::Harvested {
+            match &self.variety {
+                Some(variety) if params.get_variety(variety).filter(|&v| v != 0).is_some() => (),
+                Some(variety) => bail!("Invalid event: unknown variety {}", variety),
+                None => bail!("Invalid event: {} needs a variety", self.kind.name())
+            }
+        }
+        if self.kind == EventKind::Harvested && self.units.is_none_or(|u| u < 0) {
+            bail!("Invalid event: a harvest needs a number of units");
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let mut json = object!{
+            "event" => self.kind.name(),
+            "bed" => self.bed.clone(),
+            "week" => self.week,
+        };
+        if let Some(variety) = &self.variety {
+            json["variety"] = variety.clone().into();
+        }
+        if let Some(units) = self.units {
+            json["units"] = units.into();
+        }
+        json
+    }
+}
+
+impl TryFrom<&JsonValue> for Event {
+    type Error = Box<dyn Error>;
+
+    fn try_from(value: &JsonValue) -> Result<Self, Self::Error> {
+        let kind = match as_string(&value["event"])?.as_str() {
+            "failed" => EventKind::Failed,
+            "planted" => EventKind::Planted,
+            "removed" => EventKind::Removed,
+            "harvested" => EventKind::Harvested,
+            other => bail!("Invalid event: unknown event {}", other)
+        };
+        Ok(Event {
+            kind,
+            bed: as_string(&value["bed"])?,
+            week: as_usize(&value["week"])?,
+            variety: match value["variety"].is_null() {
+                true => None,
+                false => Some(as_string(&value["variety"])?)
+            },
+            units: match value["units"].is_null() {
+                true => None,
+                false => Some(as_int(&value["units"])?)
+            },
+        })
+    }
+}
+
+// What actually happened in a single bed week
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Actual {
+    Planted(VarietyId),
+    Failed,
+    Removed,
+}
+
+// A logged harvest
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Harvest {
+    pub bed: usize,
+    pub week: WeekId,
+    pub variety: VarietyId,
+    pub units: i32,
+}
+
+// The logged events resolved against the current beds and varieties, indexed like a Plan
+// BedPlan applies these on top of the plan, so that harvests and tasks reflect what
+// really happened. Events for beds or varieties that no longer exist are ignored
+#[derive(Clone, Debug)]
+pub struct Actuals {
+    weeks: Vec<Option<Actual>>,
+    harvests: Vec<Harvest>,
+}
+
+impl Actuals {
+    pub fn new(num_beds: usize) -> Self {
+        Actuals {
+            weeks: vec![None; num_beds*SEASON_LENGTH],
+            harvests: vec![],
+        }
+    }
+
+    pub fn from_events(events: &[Event], params: &Params) -> Self {
+        let mut actuals = Actuals::new(params.beds.len());
+        for event in events.iter().filter(|e| e.week < SEASON_LENGTH) {
+            let bed = match params.get_bed(&event.bed) {
+                Some(bed) => bed,
+                None => continue
+            };
+            let variety = match event.variety.as_ref().and_then(|v| params.get_variety(v)) {
+                Some(variety) if variety != 0 => Some(variety),
+                _ => None
+            };
+            let actual = match (event.kind, variety) {
+                (EventKind::Failed, _) => Actual::Failed,
+                (EventKind::Removed, _) => Actual::Removed,
+                (EventKind::Planted, Some(variety)) => Actual::Planted(variety),
+                (EventKind::Harvested, Some(variety)) => {
+                    let units = event.units.unwrap_or(0);
+                    actuals.harvests.push(Harvest{ bed, week: event.week, variety, units });
+                    continue;
+                },
+                (_, None) => continue
+            };
+            // Later events take precedence over earlier ones in the same week
+            actuals.weeks[bed*SEASON_LENGTH+event.week] = Some(actual);
+        }
+        actuals
+    }
+
+    pub fn get(&self, bed: usize, week: WeekId) -> Option<Actual> {
+        self.weeks.get(bed*SEASON_LENGTH+week).copied().flatten()
+    }
+
+    pub fn get_harvests(&self) -> &[Harvest] {
+        &self.harvests
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn actuals_from_events() {
+    let params = Params::dummy(
+        vec![ crate::bed::Bed::dummy("bed-0") ],
+        vec![ crate::variety::Variety::empty(), crate::variety::Variety::dummy("var-1", vec![]) ]
+    );
+    let events = vec![
+        Event{ kind: EventKind::Planted, bed: "bed-0".to_string(), week: 3, variety: Some("var-1".to_string()), units: None },
+        Event{ kind: EventKind::Failed, bed: "bed-0".to_string(), week: 8, variety: None, units: None },
+        Event{ kind: EventKind::Removed, bed: "bed-1".to_string(), week: 8, variety: None, units: None },
+        Event{ kind: EventKind::Harvested, bed: "bed-0".to_string(), week: 6, variety: Some("var-1".to_string()), units: Some(40) },
+    ];
+    for event in events.iter() {
+        let loaded = Event::try_from(&event.to_json()).expect("failed to parse");
+        assert_eq!(loaded.kind, event.kind);
+    }
+    events[2].validate(&params).expect_err("unknown bed");
+
+    let actuals = Actuals::from_events(&events, &params);
+    assert_eq!(actuals.get(0, 3), Some(Actual::Planted(1)));
+    assert_eq!(actuals.get(0, 8), Some(Actual::Failed));
+    assert_eq!(actuals.get(0, 9), None);
+    assert_eq!(actuals.get(0, 6), None);
+    assert_eq!(actuals.get_harvests(), [Harvest{ bed: 0, week: 6, variety: 1, units: 40 }]);
+}
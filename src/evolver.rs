@@ -2,11 +2,13 @@ use crate::plan::Plan;
 use crate::rand::Rand;
 use crate::params::Params;
 use crate::genome::Genome;
-use crate::constant::{POPULATION_SIZE};
-use std::io::Write;
+use crate::checkpoint::IslandCheckpoint;
+use std::error::Error;
+use simple_error::*;
 
 // Implements the evolutionary algorithm to find a planting schedule that
 // that has a high fitness according to the Evaluator
+// Each Evolver is a single population, see IslandModel for how several are combined
 pub struct Evolver<'a> {
     rand: Rand,
     params: &'a Params,
@@ -15,37 +17,69 @@ pub struct Evolver<'a> {
 
 impl<'a> Evolver<'a> {
 
-    pub fn new(params: &'a Params) -> Evolver<'a> {
-        let rand = Rand::new(&params);
-        let pop = vec!(Genome::new(&params); POPULATION_SIZE);
+    pub fn new(params: &'a Params, seed: u64) -> Result<Evolver<'a>, Box<dyn Error>> {
+        let rand = Rand::new(params, seed)?;
+        let pop = vec!(Genome::new(params); params.solver.population_size);
 
-        Evolver {
-            rand: rand,
-            params: params,
-            pop: pop,
+        Ok(Evolver {
+            rand,
+            params,
+            pop,
+        })
+    }
+
+    // Recreate a population from a checkpoint, continuing exactly where it left off
+    pub fn resume(params: &'a Params, checkpoint: &IslandCheckpoint) -> Result<Evolver<'a>, Box<dyn Error>> {
+        if checkpoint.population.len() != params.solver.population_size {
+            bail!("The checkpoint was made with a different population size");
         }
+
+        let mut rand = Rand::new(params, checkpoint.seed)?;
+        rand.set_word_pos(checkpoint.word_pos);
+        let pop = checkpoint.population
+            .iter()
+            .map(|plan| Genome::from_plan(params, plan.clone()))
+            .collect();
+
+        Ok(Evolver {
+            rand,
+            params,
+            pop,
+        })
     }
 
-    pub fn solve(&mut self) -> Plan {
-        let mut num_gens_without_improvement = 0;
-        let mut best_fitness = std::i32::MIN;
-        while num_gens_without_improvement < 1000 {
-            self.step();
-            let fitness = self.get_best_solution().to_evaluator().get_fitness();
-            if fitness > best_fitness {
-                best_fitness = fitness;
-                num_gens_without_improvement = 0;
-
-                print!(".");
-                std::io::stdout().flush().expect("internal error");
+    // Start the population from an existing plan rather than from empty plans. One individual
+    // is the plan itself and most of the others are mutated copies of it. To keep some
+    // diversity, a quarter are random plans with about as many plantings as the plan
+    pub fn warm_start(&mut self, plan: &Plan) {
+        let num_random = self.pop.len() / 4;
+        let num_mutations = std::cmp::max(1, plan.get_num_plantings());
+        for i in 0..self.pop.len() {
+            if i < num_random {
+                self.pop[i] = Genome::new(self.params);
+                for _ in 0..num_mutations {
+                    self.pop[i].mutate(&mut self.rand);
+                }
             } else {
-                num_gens_without_improvement += 1;
+                self.pop[i] = Genome::from_plan(self.params, plan.clone());
+                if i < self.pop.len() - 1 {
+                    for _ in 0..self.params.solver.mutations {
+                        self.pop[i].mutate(&mut self.rand);
+                    }
+                }
             }
         }
+        let mut population = std::mem::take(&mut self.pop);
+        self.sort_by_fitness(&mut population);
+        self.pop = population;
+    }
 
-        println!();
-
-        self.get_best_solution().to_plan()
+    pub fn get_checkpoint(&self) -> IslandCheckpoint {
+        IslandCheckpoint {
+            seed: self.rand.get_seed(),
+            word_pos: self.rand.get_word_pos(),
+            population: self.pop.iter().map(|g| g.to_plan()).collect()
+        }
     }
 
     pub fn step(&mut self) {
@@ -59,11 +93,30 @@ impl<'a> Evolver<'a> {
         self.pop.last().unwrap()
     }
 
-    fn spawn_next_generation_into(&mut self, next: &mut Vec<Genome<'a>>) {
-        // Elitism, best individual survives unchanged, means that best score can never decrease
-        next[0] = self.get_best_solution().clone();
+    // Get the plans of the fittest individuals, fittest first
+    pub fn get_emigrants(&self, count: usize) -> Vec<Plan> {
+        self.pop.iter().rev().take(count).map(|g| g.to_plan()).collect()
+    }
+
+    // Replace the least fit individuals with the given plans
+    pub fn immigrate(&mut self, plans: Vec<Plan>) {
+        for (i, plan) in plans.into_iter().enumerate() {
+            self.pop[i] = Genome::from_plan(self.params, plan);
+        }
+        let mut population = std::mem::take(&mut self.pop);
+        self.sort_by_fitness(&mut population);
+        self.pop = population;
+    }
+
+    #[allow(clippy::needless_range_loop)]
+    fn spawn_next_generation_into(&mut self, next: &mut [Genome<'a>]) {
+        // Elitism, the best individuals survive unchanged, means that best score can never decrease
+        let elitism = self.params.solver.elitism;
+        for i in 0..elitism {
+            next[i] = self.pop[self.pop.len()-1-i].clone();
+        }
 
-        for i in 2..POPULATION_SIZE {
+        for i in elitism..next.len() {
             self.spawn(&mut next[i]);
         }
     }
@@ -77,14 +130,30 @@ impl<'a> Evolver<'a> {
         Genome::cross(&self.pop[mother_id], &self.pop[father_id], child, &mut self.rand);
 
         // Mutation
-        child.mutate(&mut self.rand);
+        if self.rand.random_mutation() {
+            for _ in 0..self.params.solver.mutations {
+                child.mutate(&mut self.rand);
+            }
+        }
     }
 
     fn get_new_population(&self) -> Vec<Genome<'a>> {
-        vec!(crate::genome::Genome::new(&self.params); POPULATION_SIZE)
+        vec!(crate::genome::Genome::new(self.params); self.params.solver.population_size)
     }
 
-    fn sort_by_fitness(&mut self, population: &mut Vec<Genome<'a>>) {
+    fn sort_by_fitness(&mut self, population: &mut [Genome<'a>]) {
         population.sort_by_cached_key(|p| p.to_evaluator().get_fitness());
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+#[test]
+fn warm_start_keeps_diversity() {
+    let (params, plan) = crate::params::test_params_and_plan();
+
+    let mut evolver = Evolver::new(&params, 7).expect("failed to create");
+    evolver.warm_start(&plan);
+    let plans = evolver.get_emigrants(params.solver.population_size);
+    assert!(plans.iter().any(|p| p.get_data() == plan.get_data()));
+    assert!(plans.iter().filter(|p| p.get_data() != plan.get_data()).count() > params.solver.population_size / 2);
+}
@@ -11,8 +11,8 @@ pub struct Formatter<'a> {
 impl<'a> Formatter<'a> {
     pub fn new(params: &'a Params, plan: &'a Plan) -> Formatter<'a> {
         Formatter{
-            params: params,
-            plan: plan
+            params,
+            plan
         }
     }
 }
@@ -22,30 +22,49 @@ impl<'a> Display for Formatter<'a> {
         let evaluator = crate::evaluator::Evaluator::new(self.params, self.plan);
 
         let harvest_plan = evaluator.get_harvest_plan();
+        let stock_plan = evaluator.get_stock_plan();
 
+        // Storable varieties get a second column with the units in store at the start of the week
         write!(f, "{:>11}", "Week")?;
-        for v in 1..self.params.varieties.len() {
+        // Cover crops are never harvested, so they have no column
+        let varieties = (1..self.params.varieties.len())
+            .filter(|&v| !self.params.varieties[v].is_cover_crop())
+            .collect::<Vec<_>>();
+        for &v in varieties.iter() {
             let variety = &self.params.varieties[v];
             let mut name = variety.name.clone();
             name.truncate(9);
             write!(f, "{:>11}", name)?;
+            if variety.storage_life > 0 {
+                write!(f, "{:>11}", "(stored)")?;
+            }
         }
         writeln!(f)?;
 
         for week in 0..SEASON_LENGTH {
             write!(f, "{:>11}", week)?;
-            for v in 1..self.params.varieties.len() {
+            for &v in varieties.iter() {
+                let demand = self.params.get_demand(v, week);
                 match self.params.varieties[v].is_harvestable_in_week(week) {
-                    true => {
+                    true if demand > 0 => {
                         let harvestable_units = harvest_plan[v][week];
-                        let saturation = harvestable_units as f32  / self.params.num_baskets as f32;
+                        let saturation = harvestable_units as f32  / demand as f32;
                         write!(f, "{:>10.0}%", saturation * 100.0)?;
                     },
+                    true => {
+                        write!(f, "{:>11}", "-")?;
+                    },
                     false => {
                         write!(f, "           ")?;
                     }
 
                 }
+                if self.params.varieties[v].storage_life > 0 {
+                    match stock_plan[v][week] {
+                        0 => write!(f, "           ")?,
+                        units => write!(f, "{:>11}", units)?
+                    }
+                }
             }
             writeln!(f)?;
         }
@@ -53,6 +72,17 @@ impl<'a> Display for Formatter<'a> {
         writeln!(f)?;
         writeln!(f, "Utilization: {:.0}%", evaluator.get_bed_utilization() * 100.0)?;
         writeln!(f, "Saturation: {:.0}%", evaluator.get_market_saturation() * 100.0)?;
-        writeln!(f, "Profit: {:.2}", evaluator.get_profit() as f32 / 100.0)
+        writeln!(f, "Revenue: {:.2}", evaluator.get_revenue() as f32 / 100.0)?;
+        if self.params.channels.len() > 1 {
+            for (channel, revenue) in self.params.channels.iter().zip(evaluator.get_revenue_by_channel()) {
+                writeln!(f, "  {}: {:.2}", channel.name, revenue as f32 / 100.0)?;
+            }
+        }
+        writeln!(f, "Cost: {:.2}", evaluator.get_cost() as f32 / 100.0)?;
+        writeln!(f, "Profit: {:.2}", evaluator.get_profit() as f32 / 100.0)?;
+        match evaluator.get_soil_benefit() {
+            0 => Ok(()),
+            soil_benefit => writeln!(f, "Soil benefit: {:.2}", soil_benefit as f32 / 100.0)
+        }
     }
 }
\ No newline at end of file
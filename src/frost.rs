@@ -0,0 +1,130 @@
+use crate::common::*;
+use crate::constant::{SEASON_LENGTH, WeekId};
+use std::error::Error;
+use json::JsonValue;
+use simple_error::*;
+use regex::Regex;
+
+// Rough weeks of the last spring and first autumn frosts in USDA hardiness zones 3 to 10
+const FROST_WEEKS_BY_ZONE: [(u32, WeekId, WeekId); 8] = [
+    (3, 19, 37),
+    (4, 18, 38),
+    (5, 17, 40),
+    (6, 15, 41),
+    (7, 13, 43),
+    (8, 11, 45),
+    (9, 7, 47),
+    (10, 4, 49),
+];
+
+// The frost dates of the farm, loaded from the "frost" section of params.json, either as
+// weeks or as a climate zone. Planting schedules can give windows relative to them, so
+// that published varieties suit farms in other places
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrostDates {
+    pub last_spring: WeekId,
+    pub first_autumn: WeekId,
+}
+
+impl FrostDates {
+    pub fn try_parse(value: &JsonValue) -> Result<Self, Box<dyn Error>> {
+        let value_obj = as_object(value)?;
+        if let Some(zone) = value_obj.get("zone") {
+            // Subzones such as 6b are taken to be the whole zone
+            let zone_str = as_string(zone)?;
+            let zone_number = zone_str.trim_end_matches(|c: char| c.is_ascii_alphabetic()).parse::<u32>();
+            return match FROST_WEEKS_BY_ZONE.iter().find(|z| Ok(z.0) == zone_number) {
+                Some(&(_, last_spring, first_autumn)) => Ok(FrostDates{ last_spring, first_autumn }),
+                None => bail!("Invalid frost: unknown zone {}, give last_spring and first_autumn weeks", zone_str)
+            };
+        }
+
+        let last_spring = as_usize(&value_obj["last_spring"])?;
+        let first_autumn = as_usize(&value_obj["first_autumn"])?;
+        if last_spring >= SEASON_LENGTH || first_autumn >= SEASON_LENGTH {
+            bail!("Invalid frost: weeks must be within the season");
+        }
+        Ok(FrostDates {
+            last_spring,
+            first_autumn,
+        })
+    }
+
+    // Replace the windows relative to the frost dates in a planting schedule with weeks, e.g.
+    // "frost+2..frost+8" becomes "17-23" if the last spring frost is in week 15. "frost" is the
+    // last spring frost and "autumn_frost" the first autumn frost. Windows running over the
+    // end of the season carry on at its start
+    pub fn resolve(frost: Option<&FrostDates>, schedule: &str) -> Result<String, Box<dyn Error>> {
+        lazy_static! {
+            static ref REGEX_FROST_WINDOW: Regex = Regex::new(r"^((autumn_)?frost)?([+-]?[0-9]+)?$").unwrap();
+        }
+
+        let mut items = vec![];
+        for item in schedule.split(',').map(|i| i.trim()) {
+            if !item.contains("frost") {
+                items.push(item.to_string());
+                continue;
+            }
+            let frost = match frost {
+                Some(frost) => frost,
+                None => bail!("Invalid planting schedule {}: the frost dates are not given", item)
+            };
+
+            let mut weeks = vec![];
+            for term in item.split("..") {
+                let cap = match REGEX_FROST_WINDOW.captures(term.trim()) {
+                    Some(cap) if !term.trim().is_empty() => cap,
+                    _ => bail!("Invalid planting schedule {}", item)
+                };
+                let base = match cap.get(1).map(|m| m.as_str()) {
+                    Some("frost") => frost.last_spring as i32,
+                    Some(_) => frost.first_autumn as i32,
+                    None => 0
+                };
+                let offset = match cap.get(3) {
+                    Some(offset) => offset.as_str().parse::<i32>()?,
+                    None => 0
+                };
+                weeks.push(base + offset);
+            }
+
+            // Weeks are wrapped into the season only after this, or a whole season would be a single week
+            if let [start, end] = weeks[..] {
+                if (end - start).abs() >= SEASON_LENGTH as i32 {
+                    bail!("Invalid planting schedule {}: must be shorter than a season", item);
+                }
+            }
+            let weeks = weeks.iter().map(|w| w.rem_euclid(SEASON_LENGTH as i32)).collect::<Vec<_>>();
+
+            match weeks[..] {
+                [week] => items.push(week.to_string()),
+                [start, end] if start == end => items.push(start.to_string()),
+                [start, end] if start < end => items.push(format!("{}-{}", start, end)),
+                [start, end] => {
+                    items.push(format!("{}-{}", start, SEASON_LENGTH - 1));
+                    items.push(format!("0-{}", end));
+                },
+                _ => bail!("Invalid planting schedule {}", item)
+            }
+        }
+        Ok(items.join(","))
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn frost_windows_resolve() {
+    let frost = FrostDates::try_parse(&json::parse(r#"{ "last_spring": 15, "first_autumn": 42 }"#).unwrap()).expect("failed to parse");
+    assert_eq!(FrostDates::resolve(Some(&frost), "4-8, frost+2..frost+8").unwrap(), "4-8,17-23");
+    assert_eq!(FrostDates::resolve(Some(&frost), "frost-4,autumn_frost-6..autumn_frost").unwrap(), "11,36-42");
+    assert_eq!(FrostDates::resolve(Some(&frost), "autumn_frost+4..frost-10").unwrap(), "46-51,0-5");
+    FrostDates::resolve(None, "frost+2").expect_err("no frost dates");
+    FrostDates::resolve(Some(&frost), "frost*2").expect_err("bad window");
+    FrostDates::resolve(Some(&frost), "frost+2..").expect_err("open window");
+    FrostDates::resolve(Some(&frost), "frost..frost+52").expect_err("whole season");
+    FrostDates::resolve(Some(&frost), "frost+60..frost").expect_err("more than a season");
+
+    let zone = FrostDates::try_parse(&json::parse(r#"{ "zone": "6b" }"#).unwrap()).expect("failed to parse");
+    assert_eq!(zone, FrostDates{ last_spring: 15, first_autumn: 41 });
+    FrostDates::try_parse(&json::parse(r#"{ "zone": "13" }"#).unwrap()).expect_err("unknown zone");
+}
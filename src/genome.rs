@@ -13,14 +13,20 @@ pub struct Genome<'a> {
 
 impl Genome<'_> {
     pub fn new<'a>(params: &'a Params) -> Genome<'a> {
-        Genome {
-            plan: Plan::new(params.beds.len()),
-            params: params
-        }
+        Genome::from_plan(params, Plan::new(params.beds.len()))
+    }
+
+    pub fn from_plan(params: &Params, plan: Plan) -> Genome<'_> {
+        let mut genome = Genome {
+            plan,
+            params
+        };
+        genome.apply_locks();
+        genome
     }
 
-    pub fn to_evaluator(&self) -> Evaluator {
-        Evaluator::new(&self.params, &self.plan)
+    pub fn to_evaluator(&self) -> Evaluator<'_> {
+        Evaluator::new(self.params, &self.plan)
     }
 
     pub fn cross(mother: &Self, father: &Self, child: &mut Self, rand: &mut crate::rand::Rand) {
@@ -34,14 +40,18 @@ impl Genome<'_> {
             };
             genes_child[gene] = variety;
         }
+        child.apply_locks();
     }
 
     pub fn mutate(&mut self, rand: &mut crate::rand::Rand) {
         let gene = rand.random_gene();
+        if self.params.locks.is_locked(gene) {
+            return;
+        }
 
         let week = gene % SEASON_LENGTH;
         let bed = gene / SEASON_LENGTH;
-        let variety = rand.random_variety(week, bed).or(Some(0)).unwrap();
+        let variety = rand.random_variety(week, bed).unwrap_or(0);
 
         let end_week = std::cmp::min(SEASON_LENGTH, week+self.params.varieties[variety].get_longevity());
 
@@ -51,7 +61,20 @@ impl Genome<'_> {
             genes[(bed*SEASON_LENGTH)+w] = 0;
         }
 
-        genes[gene] = variety
+        genes[gene] = variety;
+        self.apply_locks();
+    }
+
+    // Make sure the plan respects the pinned plantings and empty weeks,
+    // and the rotation and succession rules if they must not be broken
+    fn apply_locks(&mut self) {
+        self.params.locks.apply(self.plan.get_data_mut(), self.params);
+        if self.params.rotation.is_forbidding() {
+            self.params.rotation.enforce(self.plan.get_data_mut(), self.params);
+        }
+        if self.params.succession.is_forbidding(self.params) {
+            self.params.succession.enforce(&mut self.plan, self.params);
+        }
     }
 
     pub fn to_plan(&self) -> Plan {
@@ -0,0 +1,128 @@
+use crate::plan::Plan;
+use crate::params::Params;
+use crate::bed_plan::BedPlan;
+use crate::constant::{SEASON_LENGTH, VarietyId, WeekId};
+use core::fmt::Display;
+
+// A single line of the report: what was forecast and what was logged for one variety
+// in one bed in one week
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HarvestComparison {
+    pub variety: VarietyId,
+    pub bed: usize,
+    pub week: WeekId,
+    pub forecast: i32,
+    pub actual: i32,
+}
+
+// Compares the logged harvests with the harvests forecast by the plan
+// Shows which harvest_schedule figures are off
+pub struct HarvestReport<'a> {
+    params: &'a Params,
+    plan: &'a Plan
+}
+
+impl<'a> HarvestReport<'a> {
+    pub fn new(params: &'a Params, plan: &'a Plan) -> HarvestReport<'a> {
+        HarvestReport{
+            params,
+            plan
+        }
+    }
+
+    // Every bed week with a forecast or a logged harvest, ordered by variety, week and bed.
+    // Only varieties and beds with logged harvests are compared, up to the last week a harvest
+    // of the variety was logged from the bed, so that beds nobody keeps records for don't count
+    pub fn get_comparisons(&self) -> Vec<HarvestComparison> {
+        let harvests = self.params.actuals.get_harvests();
+
+        let mut comparisons = vec![];
+        for bed in 0..self.params.beds.len() {
+            let bed_plan = BedPlan::new(bed, self.plan, self.params);
+            for bed_week in bed_plan.iter().take(SEASON_LENGTH) {
+                let is_logged_later = harvests.iter().any(|h| h.bed == bed && h.variety == bed_week.crop && h.week >= bed_week.week);
+                if bed_week.harvestable_units != 0 && is_logged_later {
+                    comparisons.push(HarvestComparison {
+                        variety: bed_week.crop,
+                        bed,
+                        week: bed_week.week,
+                        forecast: bed_week.harvestable_units,
+                        actual: 0
+                    });
+                }
+            }
+        }
+
+        for harvest in harvests.iter() {
+            let existing = comparisons.iter_mut().find(|c| c.variety == harvest.variety && c.bed == harvest.bed && c.week == harvest.week);
+            match existing {
+                Some(comparison) => comparison.actual += harvest.units,
+                None => comparisons.push(HarvestComparison {
+                    variety: harvest.variety,
+                    bed: harvest.bed,
+                    week: harvest.week,
+                    forecast: 0,
+                    actual: harvest.units
+                })
+            }
+        }
+
+        comparisons.sort_by_key(|c| (c.variety, c.week, c.bed));
+        comparisons
+    }
+}
+
+impl<'a> Display for HarvestReport<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let comparisons = self.get_comparisons();
+        if comparisons.is_empty() {
+            return writeln!(f, "No harvests have been logged. Try 'harvest log harvest'");
+        }
+
+        for variety in 1..self.params.varieties.len() {
+            let lines = comparisons.iter().filter(|c| c.variety == variety).collect::<Vec<_>>();
+            if lines.is_empty() {
+                continue;
+            }
+
+            writeln!(f, "{}\n", self.params.varieties[variety].name)?;
+            writeln!(f, "{:<9}{:<9}{:>11}{:>11}{:>11}{:>11}", "Week", "Bed", "Forecast", "Actual", "Variance", "Cumulative")?;
+            let mut cumulative = 0;
+            for line in lines.iter() {
+                let variance = line.actual - line.forecast;
+                cumulative += variance;
+                writeln!(f, "{:<9}{:<9}{:>11}{:>11}{:>11}{:>11}", line.week, self.params.beds[line.bed].name, line.forecast, line.actual, variance, cumulative)?;
+            }
+
+            let forecast: i32 = lines.iter().map(|c| c.forecast).sum();
+            let actual: i32 = lines.iter().map(|c| c.actual).sum();
+            write!(f, "{:<18}{:>11}{:>11}{:>11}", "Total", forecast, actual, actual - forecast)?;
+            if forecast != 0 {
+                write!(f, "{:>10.0}%", (actual - forecast) as f32 / forecast as f32 * 100.0)?;
+            }
+            writeln!(f, "\n")?;
+        }
+
+        write!(f, "Only beds with logged harvests are compared")
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn harvest_report_comparisons() {
+    use crate::events::{Event, EventKind, Actuals};
+
+    let (mut params, mut plan) = crate::params::test_params_and_plan();
+    let harvest = |week, units| Event{ kind: EventKind::Harvested, bed: "bed-0".to_string(), week, variety: Some("var-1".to_string()), units: Some(units) };
+    params.actuals = Actuals::from_events(&[ harvest(12, 30), harvest(12, 5), harvest(14, 10) ], &params);
+
+    plan.get_data_mut()[20] = 1;
+    plan.get_data_mut()[30] = 1;
+
+    let comparisons = HarvestReport::new(&params, &plan).get_comparisons();
+    assert_eq!(comparisons, vec![
+        HarvestComparison{ variety: 1, bed: 0, week: 12, forecast: 50, actual: 35 },
+        HarvestComparison{ variety: 1, bed: 0, week: 13, forecast: 50, actual: 0 },
+        HarvestComparison{ variety: 1, bed: 0, week: 14, forecast: 0, actual: 10 },
+    ]);
+}
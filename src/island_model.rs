@@ -0,0 +1,347 @@
+use crate::plan::Plan;
+use crate::params::Params;
+use crate::evolver::Evolver;
+use crate::checkpoint::Checkpoint;
+use crate::constant::WeekId;
+use std::error::Error;
+use simple_error::*;
+use std::io::Write;
+use std::sync::Barrier;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+// Runs several Evolver populations (islands) in parallel threads. Every migration_interval
+// generations the fittest individuals of each island migrate to the next island in a ring,
+// which helps the islands escape local optima. Islands are evolved in lock step between
+// migrations, so the result depends only on the seed and the settings, not on the number of threads
+pub struct IslandModel<'a> {
+    params: &'a Params,
+    seed: u64,
+    islands: Vec<Evolver<'a>>,
+    generation: usize,
+    num_gens_without_improvement: usize,
+    best_fitness: i32,
+    from_week: Option<WeekId>,
+}
+
+impl<'a> IslandModel<'a> {
+
+    pub fn new(params: &'a Params, seed: u64) -> Result<IslandModel<'a>, Box<dyn Error>> {
+        let islands = (0..params.solver.islands)
+            .map(|i| Evolver::new(params, seed.wrapping_add(i as u64)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(IslandModel {
+            params,
+            seed,
+            islands,
+            generation: 0,
+            num_gens_without_improvement: 0,
+            best_fitness: i32::MIN,
+            from_week: None,
+        })
+    }
+
+    // Recreate the islands from a checkpoint, continuing the run exactly where it left off
+    pub fn resume(params: &'a Params, checkpoint: &Checkpoint) -> Result<IslandModel<'a>, Box<dyn Error>> {
+        if !checkpoint.solver.is_same_run(&params.solver) {
+            bail!("The run was made with different solver settings and can't be resumed with these. Try 'harvest plan'");
+        }
+
+        let islands = checkpoint.islands
+            .iter()
+            .map(|island| Evolver::resume(params, island))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(IslandModel {
+            params,
+            seed: checkpoint.seed,
+            islands,
+            generation: checkpoint.generation,
+            num_gens_without_improvement: checkpoint.num_gens_without_improvement,
+            best_fitness: checkpoint.best_fitness,
+            from_week: checkpoint.from_week,
+        })
+    }
+
+    // Start every island from an existing plan rather than from empty plans
+    pub fn warm_start(&mut self, plan: &Plan) {
+        for island in self.islands.iter_mut() {
+            island.warm_start(plan);
+        }
+    }
+
+    // Record that the run re-plans from the given week, so that a resumed run does the same
+    pub fn set_from_week(&mut self, from_week: WeekId) {
+        self.from_week = Some(from_week);
+    }
+
+    pub fn get_from_week(&self) -> Option<WeekId> {
+        self.from_week
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn get_checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            seed: self.seed,
+            generation: self.generation,
+            num_gens_without_improvement: self.num_gens_without_improvement,
+            best_fitness: self.best_fitness,
+            islands: self.islands.iter().map(|island| island.get_checkpoint()).collect(),
+            from_week: self.from_week,
+            solver: self.params.solver.clone(),
+            revision: None,
+        }
+    }
+
+    // Evolve the islands until one of the stop criteria in the solver settings is met,
+    // or until interrupted is raised, then return the best plan found.
+    // save_checkpoint is called every checkpoint_interval generations
+    pub fn solve(&mut self, interrupted: &AtomicBool, save_checkpoint: &mut dyn FnMut(&Checkpoint)) -> Plan {
+        let solver = &self.params.solver;
+        let deadline = solver.max_time.map(|max| Instant::now() + Duration::from_secs(max));
+        let mut last_checkpoint_generation = self.generation;
+
+        let reason = loop {
+            if interrupted.load(Ordering::SeqCst) {
+                break "interrupted";
+            }
+            if self.num_gens_without_improvement >= solver.stagnation_limit {
+                break "no further improvement";
+            }
+            if solver.max_generations.is_some_and(|max| self.generation >= max) {
+                break "generation limit reached";
+            }
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                break "time limit reached";
+            }
+            if let Some(target_profit) = solver.target_profit {
+                let profit = self.get_best_island().get_best_solution().to_evaluator().get_profit();
+                if profit as f64 / 100.0 >= target_profit {
+                    break "target profit reached";
+                }
+            }
+
+            // Run up to the next migration, which a run resumed from a checkpoint saved part way
+            // through an interval finishes before it migrates, just as the run it continues would
+            let mut generations = solver.migration_interval - self.generation % solver.migration_interval;
+            if let Some(max) = solver.max_generations {
+                generations = std::cmp::min(generations, max - self.generation);
+            }
+
+            self.generation += self.evolve(generations, deadline, interrupted);
+            if self.generation % solver.migration_interval != 0 {
+                continue;
+            }
+            self.migrate();
+
+            let fitness = self.get_best_island().get_best_solution().to_evaluator().get_fitness();
+            if fitness > self.best_fitness {
+                self.best_fitness = fitness;
+                self.num_gens_without_improvement = 0;
+
+                print!(".");
+                std::io::stdout().flush().expect("internal error");
+            } else {
+                self.num_gens_without_improvement += solver.migration_interval;
+            }
+
+            if self.generation - last_checkpoint_generation >= solver.checkpoint_interval {
+                save_checkpoint(&self.get_checkpoint());
+                last_checkpoint_generation = self.generation;
+            }
+        };
+
+        println!();
+        println!("Stopped after {} generations: {}", self.generation, reason);
+
+        self.get_best_island().get_best_solution().to_plan()
+    }
+
+    // Evolve every island for the given number of generations, spreading the islands over
+    // the configured number of threads. Islands sharing a thread take turns one generation at
+    // a time. Whether to stop early, at the deadline or when interrupted is raised, is decided
+    // once per generation for all threads, so that every island has run the same number of
+    // generations. Returns the number of generations run
+    fn evolve(&mut self, generations: usize, deadline: Option<Instant>, interrupted: &AtomicBool) -> usize {
+        let threads = self.params.solver.get_threads();
+        let islands_per_thread = self.islands.len().div_ceil(threads);
+        let num_chunks = self.islands.len().div_ceil(islands_per_thread);
+        let barrier = Barrier::new(num_chunks);
+        let stop = AtomicBool::new(false);
+
+        std::thread::scope(|scope| {
+            let handles = self.islands
+                .chunks_mut(islands_per_thread)
+                .map(|chunk| {
+                    let barrier = &barrier;
+                    let stop = &stop;
+                    scope.spawn(move || {
+                        for generation in 0..generations {
+                            if barrier.wait().is_leader() {
+                                let is_stopped = interrupted.load(Ordering::SeqCst) || deadline.is_some_and(|d| Instant::now() >= d);
+                                stop.store(is_stopped, Ordering::SeqCst);
+                            }
+                            barrier.wait();
+                            if stop.load(Ordering::SeqCst) {
+                                return generation;
+                            }
+                            for island in chunk.iter_mut() {
+                                island.step();
+                            }
+                        }
+                        generations
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            let generations_run = handles.into_iter().map(|h| h.join().expect("internal error")).collect::<Vec<_>>();
+            debug_assert!(generations_run.iter().all(|&g| g == generations_run[0]));
+            generations_run[0]
+        })
+    }
+
+    // Send the fittest individuals of each island to the next island in the ring
+    fn migrate(&mut self) {
+        if self.islands.len() < 2 {
+            return;
+        }
+
+        let emigrants = self.islands
+            .iter()
+            .map(|island| island.get_emigrants(self.params.solver.migration_size))
+            .collect::<Vec<_>>();
+
+        for (i, plans) in emigrants.into_iter().enumerate() {
+            let destination = (i + 1) % self.islands.len();
+            self.islands[destination].immigrate(plans);
+        }
+    }
+
+    fn get_best_island(&self) -> &Evolver<'a> {
+        self.islands
+            .iter()
+            .max_by_key(|island| island.get_best_solution().to_evaluator().get_fitness())
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+fn test_params() -> Params {
+    let mut params = Params::dummy(
+        vec![
+            crate::bed::Bed::dummy("bed-0"),
+            crate::bed::Bed::dummy("bed-1"),
+        ],
+        vec![
+            crate::variety::Variety::empty(),
+            crate::variety::Variety::dummy("var-1", vec![]),
+        ]
+    );
+    params.varieties[1].harvest_schedule = vec![0, 0, 50, 50];
+    params.solver.stagnation_limit = 200;
+    params
+}
+
+#[cfg(test)]
+#[test]
+fn solve_is_reproducible_from_seed() {
+    let mut params = test_params();
+    params.solver.islands = 3;
+    params.solver.migration_interval = 10;
+    params.solver.stagnation_limit = 50;
+
+    let interrupted = AtomicBool::new(false);
+    params.solver.threads = Some(1);
+    let plan_a = IslandModel::new(&params, 7).expect("failed to create").solve(&interrupted, &mut |_| ());
+    params.solver.threads = Some(3);
+    let plan_b = IslandModel::new(&params, 7).expect("failed to create").solve(&interrupted, &mut |_| ());
+    assert_eq!(plan_a.get_data(), plan_b.get_data());
+}
+
+#[cfg(test)]
+#[test]
+fn solve_stops_when_interrupted() {
+    let mut params = test_params();
+    params.solver.islands = 1;
+    params.solver.stagnation_limit = usize::MAX;
+
+    let interrupted = AtomicBool::new(true);
+    let plan = IslandModel::new(&params, 7).expect("failed to create").solve(&interrupted, &mut |_| ());
+    assert_eq!(plan.get_num_plantings(), 0);
+
+    params.solver.max_generations = Some(10);
+    let interrupted = AtomicBool::new(false);
+    let plan = IslandModel::new(&params, 7).expect("failed to create").solve(&interrupted, &mut |_| ());
+    assert!(plan.get_num_plantings() > 0);
+}
+
+#[cfg(test)]
+#[test]
+fn resume_continues_run() {
+    let mut params = test_params();
+    params.solver.islands = 2;
+    params.solver.threads = Some(2);
+    params.solver.migration_interval = 10;
+    params.solver.stagnation_limit = usize::MAX;
+    params.solver.max_generations = Some(40);
+    params.solver.checkpoint_interval = 10;
+    let interrupted = AtomicBool::new(false);
+
+    let mut checkpoint = None;
+    let plan_uninterrupted = IslandModel::new(&params, 7).expect("failed to create").solve(&interrupted, &mut |c| {
+        if c.generation == 20 {
+            checkpoint = Some(c.clone());
+        }
+    });
+    let checkpoint = checkpoint.expect("no checkpoint written");
+
+    // Threads don't change the run, but the other settings do
+    params.solver.threads = Some(1);
+    params.solver.max_generations = Some(50);
+    assert!(IslandModel::resume(&params, &checkpoint).is_err());
+
+    params.solver.max_generations = Some(40);
+    let plan_resumed = IslandModel::resume(&params, &checkpoint)
+        .expect("failed to resume")
+        .solve(&interrupted, &mut |_| ());
+    assert_eq!(plan_uninterrupted.get_data(), plan_resumed.get_data());
+}
+
+#[cfg(test)]
+#[test]
+fn resume_continues_interrupted_run() {
+    let mut params = test_params();
+    params.solver.islands = 3;
+    params.solver.migration_interval = 40;
+    params.solver.stagnation_limit = usize::MAX;
+    params.solver.max_generations = Some(60);
+
+    params.solver.threads = Some(1);
+    let start = Instant::now();
+    let plan_uninterrupted = IslandModel::new(&params, 7).expect("failed to create").solve(&AtomicBool::new(false), &mut |_| ());
+    let duration = start.elapsed();
+
+    // Interrupt about a third of the way through, part way through the first migration interval
+    params.solver.threads = Some(3);
+    let interrupted = AtomicBool::new(false);
+    let mut island_model = IslandModel::new(&params, 7).expect("failed to create");
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            std::thread::sleep(duration / 3);
+            interrupted.store(true, Ordering::SeqCst);
+        });
+        island_model.solve(&interrupted, &mut |_| ());
+    });
+    let checkpoint = island_model.get_checkpoint();
+    assert!(checkpoint.generation < 60);
+
+    params.solver.threads = Some(2);
+    let plan_resumed = IslandModel::resume(&params, &checkpoint)
+        .expect("failed to resume")
+        .solve(&AtomicBool::new(false), &mut |_| ());
+    assert_eq!(plan_uninterrupted.get_data(), plan_resumed.get_data());
+}
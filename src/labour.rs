@@ -0,0 +1,117 @@
+use crate::common::*;
+use crate::constant::SEASON_LENGTH;
+use std::error::Error;
+use json::JsonValue;
+use simple_error::*;
+
+const DEFAULT_PENALTY_PER_HOUR: i32 = 2000;
+
+// The time an instruction takes, in minutes
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Labour {
+    // For a bed of the standard size, see Params::get_yield_scale
+    PerBed(f64),
+    // For each harvested unit
+    PerUnit(f64),
+}
+
+impl Labour {
+    pub fn get_minutes(&self, yield_scale: f64, units: i32) -> f64 {
+        match self {
+            Labour::PerBed(minutes) => minutes * yield_scale,
+            Labour::PerUnit(minutes) => minutes * units as f64,
+        }
+    }
+}
+
+// The hours of work available each week, loaded from the "labour" section of params.json
+// Each hour of work beyond them costs the penalty in the fitness
+#[derive(Clone, Debug)]
+pub struct LabourCapacity {
+    pub hours: Vec<f64>,
+    pub penalty_per_hour: i32,
+}
+
+impl LabourCapacity {
+    // The hours per week are either a number for every week, or a schedule like
+    // a harvest_schedule, where "40:10,60:30" is 40 hours for 10 weeks then 60 for 30.
+    // Weeks beyond the end of the schedule have the last week's hours
+    pub fn try_parse(value: &JsonValue) -> Result<Self, Box<dyn Error>> {
+        let value_obj = as_object(value)?;
+        let mut hours = match &value_obj["hours_per_week"] {
+            JsonValue::String(_) | JsonValue::Short(_) => parse_hours(&as_string(&value_obj["hours_per_week"])?)?,
+            hours_json => vec![as_f64(hours_json)?]
+        };
+        if hours.is_empty() || hours.len() > SEASON_LENGTH {
+            bail!("Invalid labour: hours_per_week must cover between 1 and {} weeks", SEASON_LENGTH);
+        }
+        let last = hours[hours.len()-1];
+        hours.resize(SEASON_LENGTH, last);
+
+        let penalty_per_hour = match value_obj.get("penalty_per_hour") {
+            Some(penalty) => as_int(penalty)?,
+            None => DEFAULT_PENALTY_PER_HOUR
+        };
+        if penalty_per_hour < 0 {
+            bail!("Invalid labour: penalty_per_hour must not be negative");
+        }
+
+        Ok(LabourCapacity {
+            hours,
+            penalty_per_hour,
+        })
+    }
+
+    // Hours of work beyond the capacity over the season, given the minutes of work in each week
+    pub fn get_overload(&self, workload: &[f64]) -> f64 {
+        workload
+            .iter()
+            .zip(self.hours.iter())
+            .map(|(minutes, hours)| (minutes / 60.0 - hours).max(0.0))
+            .sum()
+    }
+}
+
+// Parse a schedule of hours like a harvest_schedule, except that the hours may have decimals,
+// e.g. "37.5:10,20" is 37.5 hours for 10 weeks then 20
+fn parse_hours(input: &str) -> Result<Vec<f64>, Box<dyn Error>> {
+    let mut result = vec![];
+    for item in input.split(',').map(|i| i.trim()) {
+        let (hours, weeks) = match item.split_once(':') {
+            Some((hours, weeks)) => (hours.trim(), weeks.trim().parse::<usize>().ok()),
+            None => (item, Some(1))
+        };
+        match (hours.parse::<f64>(), weeks) {
+            (Ok(hours), Some(weeks)) if hours.is_finite() && hours >= 0.0 && weeks <= SEASON_LENGTH => result.extend(std::iter::repeat_n(hours, weeks)),
+            _ => bail!("Invalid labour: hours_per_week {} must be hours, optionally followed by a number of weeks", item)
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+#[test]
+fn labour_capacity_from_json() {
+    let js = json::parse(r#"{ "hours_per_week": "40:10,60:2" }"#).expect("test is wrong");
+    let capacity = LabourCapacity::try_parse(&js).expect("failed to parse");
+    assert_eq!(capacity.hours[9], 40.0);
+    assert_eq!(capacity.hours[10], 60.0);
+    assert_eq!(capacity.hours[51], 60.0);
+    assert_eq!(capacity.penalty_per_hour, DEFAULT_PENALTY_PER_HOUR);
+
+    let js = json::parse(r#"{ "hours_per_week": "37.5:10, 20" }"#).expect("test is wrong");
+    let capacity = LabourCapacity::try_parse(&js).expect("failed to parse");
+    assert_eq!(capacity.hours[9], 37.5);
+    assert_eq!(capacity.hours[10], 20.0);
+    let js = json::parse(r#"{ "hours_per_week": "40;10" }"#).expect("test is wrong");
+    LabourCapacity::try_parse(&js).expect_err("not a schedule");
+    let js = json::parse(r#"{ "hours_per_week": "40:ten" }"#).expect("test is wrong");
+    LabourCapacity::try_parse(&js).expect_err("not a number of weeks");
+
+    let js = json::parse(r#"{ "hours_per_week": 20, "penalty_per_hour": 100 }"#).expect("test is wrong");
+    let capacity = LabourCapacity::try_parse(&js).expect("failed to parse");
+    let mut workload = vec![0.0; SEASON_LENGTH];
+    workload[3] = 30.0 * 60.0;
+    workload[4] = 10.0 * 60.0;
+    assert_eq!(capacity.get_overload(&workload), 10.0);
+}
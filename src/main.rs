@@ -13,6 +13,23 @@ mod tasks;
 mod evaluator;
 mod formatter;
 mod plan;
+mod solver_params;
+mod island_model;
+mod checkpoint;
+mod pin;
+mod revision;
+mod events;
+mod harvest_report;
+mod channel;
+mod rotation;
+mod succession;
+mod labour;
+mod workload_report;
+mod nursery;
+mod nursery_report;
+mod risk;
+mod risk_report;
+mod frost;
 
 #[macro_use] extern crate lazy_static;
 
@@ -28,22 +45,25 @@ fn main() {
 
     let result = match opt.command {
         Cmd::Init(params) => init(&params),
-        Cmd::Solve => solve(),
+        Cmd::Solve(params) => solve(&params),
+        Cmd::Replan(params) => replan(&params),
         Cmd::Reset => reset(),
         Cmd::Print(params) => print(&params),
+        Cmd::Log(cmd) => log(&cmd),
+        Cmd::Report(cmd) => report(&cmd),
+        Cmd::Pin(params) => pin(&params),
+        Cmd::Check => check(),
+        Cmd::Risk(params) => risk(&params),
     };
 
-    match result {
-        Err(msg) => eprintln!("{}", msg),
-        _ => ()
-    };
+    if let Err(msg) = result { eprintln!("{}", msg) };
 }
 
 fn init(params: &ParamsInit) -> Result<(), Box<dyn std::error::Error>> {
     let mut repo = Repo::new(&std::path::PathBuf::from("."));
     match &params.cont {
         None => repo.init()?,
-        Some(continue_path) => repo.init_continue(&continue_path)?
+        Some(continue_path) => repo.init_continue(continue_path)?
     };
     Ok(())
 }
@@ -51,15 +71,117 @@ fn init(params: &ParamsInit) -> Result<(), Box<dyn std::error::Error>> {
 fn reset() -> Result<(), Box<dyn std::error::Error>> {
     let mut repo = Repo::new(&std::path::PathBuf::from("."));
     repo.load()?;
-    repo.reset();
+    repo.reset()?;
     repo.save()
 }
 
+fn log(cmd: &crate::cli::LogCmd) -> Result<(), Box<dyn std::error::Error>> {
+    use crate::events::{Event, EventKind};
+    let event = match cmd {
+        LogCmd::Fail(p) => Event{ kind: EventKind::Failed, bed: p.bed.clone(), week: p.week, variety: None, units: None },
+        LogCmd::Planted(p) => Event{ kind: EventKind::Planted, bed: p.bed.clone(), week: p.week, variety: Some(p.variety.clone()), units: None },
+        LogCmd::Removed(p) => Event{ kind: EventKind::Removed, bed: p.bed.clone(), week: p.week, variety: None, units: None },
+        LogCmd::Harvest(p) => Event{ kind: EventKind::Harvested, bed: p.bed.clone(), week: p.week, variety: Some(p.variety.clone()), units: Some(p.units) },
+    };
+    let is_harvest = event.kind == EventKind::Harvested;
+
+    let mut repo = require_repo()?;
+    let params = repo.get_params()?;
+    repo.put_event(event, &params)?;
+    repo.save()?;
+    match is_harvest {
+        true => println!("Logged. Try 'harvest report harvests' to compare with the forecast"),
+        false => println!("Logged. Try 'harvest replan --from-week <week>' to make the most of the change")
+    }
+    Ok(())
+}
+
+fn report(cmd: &crate::cli::ReportCmd) -> Result<(), Box<dyn std::error::Error>> {
+    let repo = require_repo()?;
+    let plan = repo.require_plan()?;
+    let params = repo.get_params()?;
+    match cmd {
+        ReportCmd::Harvests => println!("{}", crate::harvest_report::HarvestReport::new(&params, plan)),
+        ReportCmd::Workload => println!("{}", crate::workload_report::WorkloadReport::new(&params, plan)),
+        ReportCmd::Nursery => println!("{}", crate::nursery_report::NurseryReport::new(&params, plan)),
+    }
+    Ok(())
+}
+
+fn pin(cmd: &crate::cli::ParamsPin) -> Result<(), Box<dyn std::error::Error>> {
+    let mut repo = require_repo()?;
+    if cmd.remove {
+        match repo.remove_pins(&cmd.bed, cmd.week) {
+            0 => bail!("There is no pin for bed {} in week {}", cmd.bed, cmd.week),
+            _ => println!("Unpinned. Try 'harvest plan --from-current' to update the plan")
+        }
+        return repo.save();
+    }
+
+    let mut pin_json = json::object!{
+        "bed" => cmd.bed.clone(),
+        "week" => cmd.week,
+    };
+    if let Some(variety) = &cmd.variety {
+        pin_json["variety"] = variety.clone().into();
+    }
+    if let Some(until) = cmd.until {
+        pin_json["until"] = until.into();
+    }
+    repo.put_pin(pin_json)?;
+    repo.save()?;
+    println!("Pinned. Try 'harvest plan --from-current' to update the plan");
+    Ok(())
+}
+
+fn risk(cmd: &crate::cli::ParamsRisk) -> Result<(), Box<dyn std::error::Error>> {
+    if cmd.simulations == 0 {
+        bail!("There must be at least one simulation");
+    }
+    let repo = require_repo()?;
+    let plan = repo.require_plan()?;
+    let params = repo.get_params()?;
+    println!("{}", crate::risk_report::RiskReport::simulate(&params, plan, cmd.simulations, cmd.seed));
+    Ok(())
+}
+
+fn check() -> Result<(), Box<dyn std::error::Error>> {
+    let repo = require_repo()?;
+    let plan = repo.require_plan()?;
+    let params = repo.get_params()?;
+    let violations = params.rotation.find_violations(plan, &params);
+    let avoided = params.succession.find_avoided(plan, &params);
+    if violations.is_empty() && avoided.is_empty() {
+        println!("No problems found");
+    }
+    for v in violations.iter() {
+        let variety = &params.varieties[v.variety];
+        println!("Bed {} week {}: {} ({}) only {} weeks after {} in week {}",
+            params.beds[v.bed].name,
+            v.week,
+            variety.name,
+            variety.family.as_deref().unwrap_or(""),
+            v.week - v.previous_week,
+            params.varieties[v.previous_variety].name,
+            v.previous_week
+        );
+    }
+    for s in avoided.iter() {
+        println!("Bed {} week {}: {} follows {}, which it should avoid",
+            params.beds[s.bed].name,
+            s.week,
+            params.varieties[s.variety].name,
+            params.varieties[s.predecessor].name
+        );
+    }
+    Ok(())
+}
+
 fn print(cmd: &crate::cli::ParamsPrint) -> Result<(), Box<dyn std::error::Error>> {
     match &cmd.bed {
         Some(bed) => match cmd.week {
-            Some(week) => print_bed_week(&bed, week),
-            None => print_bed(&bed)
+            Some(week) => print_bed_week(bed, week),
+            None => print_bed(bed)
         }
         None => match cmd.week {
             Some(week) => print_week(week),
@@ -68,18 +190,20 @@ fn print(cmd: &crate::cli::ParamsPrint) -> Result<(), Box<dyn std::error::Error>
     }
 }
 
-fn print_bed(bed_name: &std::string::String) -> Result<(), Box<dyn std::error::Error>> {
+fn print_bed(bed_name: &str) -> Result<(), Box<dyn std::error::Error>> {
     let repo = require_repo()?;
     let plan = repo.require_plan()?;
     
     let params = repo.get_params()?;
-    let bed = require_bed(bed_name, &params)?;
-    let bed_plan = crate::bed_plan::BedPlan::new(bed, plan, &params);
-    println!("{}", bed_plan);
+    // A bed split into sections is printed section by section
+    for bed in require_beds(bed_name, &params)? {
+        let bed_plan = crate::bed_plan::BedPlan::new(bed, plan, &params);
+        println!("{}", bed_plan);
+    }
     Ok(())
 }
 
-fn print_bed_week(bed: &std::string::String, week: usize) -> Result<(), Box<dyn std::error::Error>> {
+fn print_bed_week(bed: &str, week: usize) -> Result<(), Box<dyn std::error::Error>> {
     println!("Bed {}, Week {}", bed, week);
     Ok(()) // TODO
 }
@@ -88,7 +212,7 @@ fn print_week(week: usize) -> Result<(), Box<dyn std::error::Error>> {
     let repo = require_repo()?;
     let plan = repo.require_plan()?;
     let params = repo.get_params()?;
-    let evaluator = crate::evaluator::Evaluator::new(&params, &plan);
+    let evaluator = crate::evaluator::Evaluator::new(&params, plan);
     let tasks = evaluator.get_tasks();    
     let week_instructions = tasks.get(week);
 
@@ -105,15 +229,23 @@ fn print_solution() -> Result<(), Box<dyn std::error::Error>> {
     let repo = require_repo()?;
     let sol = repo.require_plan()?;
     let params = repo.get_params()?;
-    let formatter = crate::formatter::Formatter::new(&params, &sol);
+    let formatter = crate::formatter::Formatter::new(&params, sol);
     println!("{}", &formatter);
+    if let Some(revision) = repo.get_current_revision() {
+        if let Some(seed) = revision.seed {
+            println!("Seed: {}", seed);
+        }
+        if let Some(from_week) = revision.from_week {
+            println!("Revision {}, re-planned from week {}", repo.get_revision_number(), from_week);
+        }
+    }
     Ok(())
 }
 
-fn require_bed(name: &std::string::String, params: &crate::params::Params) -> Result<usize, Box<dyn std::error::Error>> {
-    match params.get_bed(name) {
-        Some(i) => Ok(i),
-        None => bail!("Unknown bed")
+fn require_beds(name: &str, params: &crate::params::Params) -> Result<Vec<usize>, Box<dyn std::error::Error>> {
+    match params.get_beds_or_sections(name) {
+        beds if beds.is_empty() => bail!("Unknown bed"),
+        beds => Ok(beds)
     }
 }
 
@@ -123,17 +255,134 @@ fn require_repo() -> Result<crate::repo::Repo, Box<dyn std::error::Error>> {
     Ok(repo)
 }
 
-fn solve() -> Result<(), Box<dyn std::error::Error>> {
+fn solve(cmd: &crate::cli::ParamsSolve) -> Result<(), Box<dyn std::error::Error>> {
     let mut repo = Repo::new(&std::path::PathBuf::from("."));
     repo.load()?;
-    repo.require_no_solution()?;
 
-    let params = repo.get_params()?;
-    let mut evolver = crate::evolver::Evolver::new(&params);
-    let solution = evolver.solve();
+    let mut params = repo.get_params()?;
+    apply_solver_overrides(&cmd.solver, &mut params.solver)?;
+
+    let mut replace_current = false;
+    let mut island_model = if cmd.resume {
+        let checkpoint = repo.require_checkpoint()?;
+        // The best plan of an interrupted run has been stored, and the resumed run replaces it
+        if repo.get_current_revision().is_some() && checkpoint.revision == Some(repo.get_revision_number()) {
+            replace_current = true;
+        } else if checkpoint.from_week.is_none() {
+            repo.require_no_solution()?;
+        }
+        println!("Resuming from generation {}", checkpoint.generation);
+        if let Some(from_week) = checkpoint.from_week {
+            params.freeze_before(&repo.require_current_plan_for(&params)?, from_week);
+        }
+        crate::island_model::IslandModel::resume(&params, &checkpoint)?
+    } else {
+        repo.require_no_solution()?;
+        let seed = cmd.seed.unwrap_or_else(crate::rand::Rand::random_seed);
+        let mut island_model = crate::island_model::IslandModel::new(&params, seed)?;
+        if cmd.from_current {
+            island_model.warm_start(&repo.require_current_plan_for(&params)?);
+        }
+        island_model
+    };
+    run_solver(&mut island_model, &mut repo, &params, replace_current)
+}
+
+// Re-design the current plan from the given week on, keeping everything before it
+fn replan(cmd: &crate::cli::ParamsReplan) -> Result<(), Box<dyn std::error::Error>> {
+    let mut repo = Repo::new(&std::path::PathBuf::from("."));
+    repo.load()?;
+
+    if cmd.from_week >= crate::constant::SEASON_LENGTH {
+        bail!("Invalid week {}", cmd.from_week);
+    }
+
+    let mut params = repo.get_params()?;
+    apply_solver_overrides(&cmd.solver, &mut params.solver)?;
+    let current_plan = repo.require_current_plan_for(&params)?;
+    params.freeze_before(&current_plan, cmd.from_week);
+
+    let seed = cmd.seed.unwrap_or_else(crate::rand::Rand::random_seed);
+    let mut island_model = crate::island_model::IslandModel::new(&params, seed)?;
+    island_model.warm_start(&current_plan);
+    island_model.set_from_week(cmd.from_week);
+    println!("Re-planning from week {}", cmd.from_week);
+
+    run_solver(&mut island_model, &mut repo, &params, false)
+}
+
+// Run the solver until it stops or is interrupted and store the resulting plan, either as a
+// new revision or in place of the current one. Only an interrupted run keeps its checkpoint
+fn run_solver(island_model: &mut crate::island_model::IslandModel, repo: &mut Repo, params: &crate::params::Params, replace_current: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let seed = island_model.get_seed();
+    println!("Seed: {}", seed);
 
-    repo.put_solution(solution)?;
+    // Ctrl-C stops the solver early, the best plan found so far is kept
+    let interrupted = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let handler_interrupted = interrupted.clone();
+    ctrlc::set_handler(move || handler_interrupted.store(true, std::sync::atomic::Ordering::SeqCst))?;
+
+    let solution = island_model.solve(&interrupted, &mut |checkpoint| {
+        if let Err(e) = repo.put_checkpoint(checkpoint) {
+            eprintln!("Failed to save checkpoint: {}", e);
+        }
+    });
+    let is_interrupted = interrupted.load(std::sync::atomic::Ordering::SeqCst);
+    if is_interrupted {
+        println!("Saving the best plan found so far");
+    }
+
+    match replace_current {
+        true => repo.replace_solution(solution, seed, island_model.get_from_week(), params)?,
+        false => repo.put_solution(solution, seed, island_model.get_from_week(), params)?
+    }
     repo.save()?;
 
+    if is_interrupted {
+        let mut checkpoint = island_model.get_checkpoint();
+        checkpoint.revision = Some(repo.get_revision_number());
+        repo.put_checkpoint(&checkpoint)?;
+        println!("Continue the run with 'harvest plan --resume'");
+    } else {
+        repo.clear_checkpoint()?;
+    }
+
     Ok(())
 }
+
+fn apply_solver_overrides(cmd: &crate::cli::SolverOptions, solver: &mut crate::solver_params::SolverParams) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(population_size) = cmd.population_size {
+        solver.population_size = population_size;
+    }
+    if let Some(stagnation_limit) = cmd.stagnation_limit {
+        solver.stagnation_limit = stagnation_limit;
+    }
+    if let Some(mutations) = cmd.mutations {
+        solver.mutations = mutations;
+    }
+    if let Some(mutation_rate) = cmd.mutation_rate {
+        solver.mutation_rate = mutation_rate;
+    }
+    if let Some(elitism) = cmd.elitism {
+        solver.elitism = elitism;
+    }
+    if let Some(selection_pressure) = cmd.selection_pressure {
+        solver.selection_pressure = selection_pressure;
+    }
+    if cmd.max_generations.is_some() {
+        solver.max_generations = cmd.max_generations;
+    }
+    if cmd.max_time.is_some() {
+        solver.max_time = cmd.max_time;
+    }
+    if cmd.target_profit.is_some() {
+        solver.target_profit = cmd.target_profit;
+    }
+    if let Some(islands) = cmd.islands {
+        solver.islands = islands;
+    }
+    if cmd.threads.is_some() {
+        solver.threads = cmd.threads;
+    }
+    solver.validate()
+}
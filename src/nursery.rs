@@ -0,0 +1,113 @@
+use crate::common::*;
+use crate::constant::{SEASON_LENGTH, VarietyId, WeekId, WeekRange};
+use std::error::Error;
+use json::JsonValue;
+use simple_error::*;
+
+const DEFAULT_PENALTY_PER_TRAY: i32 = 500;
+
+// How a variety is raised before it is planted out, loaded from its "propagation"
+// The weeks it spends in the nursery come from its earliest instruction, see
+// Variety::get_nursery_weeks
+#[derive(Clone, Debug, PartialEq)]
+pub struct Propagation {
+    pub tray: String,
+    // For a bed of the standard size, see Params::get_yield_scale
+    pub trays_per_bed: f64,
+}
+
+impl Propagation {
+    pub fn try_parse(value: &JsonValue) -> Result<Self, Box<dyn Error>> {
+        let value_obj = as_object(value)?;
+        let trays_per_bed = as_f64(&value_obj["trays_per_bed"])?;
+        if trays_per_bed <= 0.0 {
+            bail!("Invalid propagation: trays_per_bed must be positive");
+        }
+        Ok(Propagation {
+            tray: as_string(&value_obj["tray"])?,
+            trays_per_bed,
+        })
+    }
+}
+
+// A planting raised in the nursery, which takes up trays from from_week until it is
+// planted out in the bed in its planting week
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sowing {
+    pub bed: usize,
+    pub variety: VarietyId,
+    pub trays: f64,
+    pub from_week: WeekId,
+    pub weeks: WeekRange,
+}
+
+impl Sowing {
+    // The weeks the trays are in the nursery, wrapped into the season
+    pub fn get_weeks(&self) -> impl Iterator<Item = WeekId> + '_ {
+        (0..self.weeks).map(move |w| (self.from_week + w) % SEASON_LENGTH)
+    }
+}
+
+// The trays of each type that fit in the nursery, loaded from the "nursery" section of
+// params.json. Each tray beyond them in a week costs the penalty in the fitness
+#[derive(Clone, Debug)]
+pub struct Nursery {
+    pub trays: Vec<String>,
+    pub capacity: Vec<f64>,
+    pub penalty_per_tray: i32,
+}
+
+impl Nursery {
+    pub fn try_parse(value: &JsonValue) -> Result<Self, Box<dyn Error>> {
+        let value_obj = as_object(value)?;
+        let mut trays = vec![];
+        let mut capacity = vec![];
+        for (tray, tray_capacity) in as_object(&value_obj["capacity"])?.iter() {
+            trays.push(tray.to_string());
+            capacity.push(as_f64(tray_capacity)?);
+        }
+
+        let penalty_per_tray = match value_obj.get("penalty_per_tray") {
+            Some(penalty) => as_int(penalty)?,
+            None => DEFAULT_PENALTY_PER_TRAY
+        };
+        if penalty_per_tray < 0 {
+            bail!("Invalid nursery: penalty_per_tray must not be negative");
+        }
+
+        Ok(Nursery {
+            trays,
+            capacity,
+            penalty_per_tray,
+        })
+    }
+
+    pub fn get_tray(&self, name: &str) -> Option<usize> {
+        self.trays.iter().position(|t| t == name)
+    }
+
+    // Trays beyond the capacity, summed over the weeks, given the trays of each type in each week
+    pub fn get_overload(&self, occupancy: &[Vec<f64>]) -> f64 {
+        occupancy
+            .iter()
+            .zip(self.capacity.iter())
+            .map(|(weeks, capacity)| weeks.iter().map(|trays| (trays - capacity).max(0.0)).sum::<f64>())
+            .sum()
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn nursery_from_json() {
+    let js = json::parse(r#"{ "capacity": { "144": 40, "72": 10 }, "penalty_per_tray": 100 }"#).expect("test is wrong");
+    let nursery = Nursery::try_parse(&js).expect("failed to parse");
+    let tray = nursery.get_tray("72").expect("missing tray");
+    let mut occupancy = vec![vec![0.0; SEASON_LENGTH]; 2];
+    occupancy[tray][3] = 12.0;
+    occupancy[tray][4] = 10.0;
+    assert_eq!(nursery.get_overload(&occupancy), 2.0);
+    assert_eq!(nursery.penalty_per_tray, 100);
+
+    let sowing = Sowing{ bed: 0, variety: 1, trays: 2.0, from_week: 50, weeks: 4 };
+    assert_eq!(sowing.get_weeks().collect::<Vec<_>>(), vec![50, 51, 0, 1]);
+}
@@ -0,0 +1,68 @@
+use crate::plan::Plan;
+use crate::params::Params;
+use crate::evaluator::Evaluator;
+use crate::constant::SEASON_LENGTH;
+use core::fmt::Display;
+
+// The trays of each type in the nursery each week against its capacity, followed by
+// what is sown each week. Weeks over capacity are marked with '!'
+pub struct NurseryReport<'a> {
+    params: &'a Params,
+    plan: &'a Plan
+}
+
+impl<'a> NurseryReport<'a> {
+    pub fn new(params: &'a Params, plan: &'a Plan) -> NurseryReport<'a> {
+        NurseryReport{
+            params,
+            plan
+        }
+    }
+}
+
+impl<'a> Display for NurseryReport<'a> {
+    #[allow(clippy::needless_range_loop)]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let evaluator = Evaluator::new(self.params, self.plan);
+        let mut sowings = evaluator.get_sowings();
+        if sowings.is_empty() {
+            return writeln!(f, "Nothing is raised in the nursery. Give the varieties a propagation");
+        }
+
+        if let Some(nursery) = &self.params.nursery {
+            let occupancy = evaluator.get_nursery_occupancy(nursery);
+            write!(f, "{:<6}", "Week")?;
+            for tray in nursery.trays.iter() {
+                write!(f, "{:>16}", tray)?;
+            }
+            writeln!(f)?;
+            for week in 0..SEASON_LENGTH {
+                write!(f, "{:<6}", week)?;
+                for tray in 0..nursery.trays.len() {
+                    let trays = occupancy[tray][week];
+                    let mark = if trays > nursery.capacity[tray] { "!" } else { " " };
+                    write!(f, "{:>15}{}", format!("{:.1}/{}", trays, nursery.capacity[tray]), mark)?;
+                }
+                writeln!(f)?;
+            }
+            writeln!(f)?;
+            writeln!(f, "Over capacity: {:.1} tray weeks\n", nursery.get_overload(&occupancy))?;
+        }
+
+        sowings.sort_by_key(|s| (s.from_week, s.bed));
+        writeln!(f, "{:<6}{:<25}{:>8} {:<7}{:<9}{:>9}", "Week", "Variety", "Trays", "Type", "Bed", "Out")?;
+        for sowing in sowings.iter() {
+            let variety = &self.params.varieties[sowing.variety];
+            let tray = &variety.propagation.as_ref().unwrap().tray;
+            writeln!(f, "{:<6}{:<25}{:>8.1} {:<7}{:<9}{:>9}",
+                sowing.from_week,
+                variety.name,
+                sowing.trays,
+                tray,
+                self.params.beds[sowing.bed].name,
+                (sowing.from_week + sowing.weeks) % SEASON_LENGTH
+            )?;
+        }
+        Ok(())
+    }
+}
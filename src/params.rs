@@ -1,11 +1,25 @@
 use crate::plan::Plan;
+use crate::constant::SEASON_LENGTH;
 use std::error::Error;
-use crate::bed::Bed;
+use crate::bed::{Bed, Turnaround};
 use crate::variety::Variety;
+use crate::solver_params::SolverParams;
+use crate::pin::{Pin, Locks};
+use crate::events::Actuals;
+use crate::channel::Channel;
+use crate::rotation::Rotation;
+use crate::succession::SuccessionRules;
+use crate::labour::LabourCapacity;
+use crate::nursery::Nursery;
+use crate::risk::RiskObjective;
+use crate::frost::FrostDates;
+use simple_error::*;
 use crate::common::*;
 use std::convert::TryFrom;
 use json::JsonValue;
 
+const DEFAULT_STANDARD_BED_AREA: f64 = 30.0;
+
 // Represents all of the input parameters to the plan building algorithm
 // Loaded from params.json
 #[derive(Clone)]
@@ -13,7 +27,27 @@ pub struct Params {
     pub beds: Vec<Bed>,
     pub varieties: Vec<Variety>,
     pub num_baskets: i32,
+    // Area in square metres that the varieties' harvest schedules are given for
+    pub standard_bed_area: f64,
+    pub channels: Vec<Channel>,
     pub plan_previous_year: Plan,
+    // Plans of the seasons before the previous one, most recent first
+    pub plans_earlier_years: Vec<Plan>,
+    pub rotation: Rotation,
+    pub succession: SuccessionRules,
+    // The work that can be done each week, unlimited if not given
+    pub labour: Option<LabourCapacity>,
+    // The trays that fit in the nursery, unlimited if not given
+    pub nursery: Option<Nursery>,
+    pub turnaround: Turnaround,
+    // Optimise a risk-adjusted profit rather than the profit of the harvest_schedules
+    pub risk: Option<RiskObjective>,
+    pub solver: SolverParams,
+    pub pins: Vec<Pin>,
+    pub locks: Locks,
+    // What actually happened, as logged against the plan. Not part of params.json,
+    // the Repo fills this in from its logged events
+    pub actuals: Actuals,
 }
 
 impl TryFrom<&JsonValue> for Params {
@@ -24,34 +58,230 @@ impl TryFrom<&JsonValue> for Params {
             varieties: vec![],
             beds: vec![],
             num_baskets: 0,
-            plan_previous_year: Plan::new(0)
+            standard_bed_area: DEFAULT_STANDARD_BED_AREA,
+            channels: vec![],
+            plan_previous_year: Plan::new(0),
+            plans_earlier_years: vec![],
+            rotation: Rotation::default(),
+            succession: SuccessionRules::default(),
+            labour: None,
+            nursery: None,
+            turnaround: Turnaround::default(),
+            risk: None,
+            solver: SolverParams::default(),
+            pins: vec![],
+            locks: Locks::new(0),
+            actuals: Actuals::new(0)
         };
 
         let value_json_obj = as_object(value)?;
         params.num_baskets = as_int(&value_json_obj["num_baskets"])?;
+        if let Some(area) = value_json_obj.get("standard_bed_area") {
+            params.standard_bed_area = as_f64(area)?;
+            if params.standard_bed_area <= 0.0 {
+                bail!("Invalid standard_bed_area: must be positive");
+            }
+        }
         let varieties_json_array = as_array(&value_json_obj["varieties"])?;
-        params.varieties = varieties_json_array.iter().map(|j| Variety::try_parse(j)).collect::<Result<Vec<_>, _>>()?;
+        let frost = match value_json_obj.get("frost") {
+            Some(frost_json_obj) => Some(FrostDates::try_parse(frost_json_obj)?),
+            None => None
+        };
+        params.varieties = varieties_json_array.iter().map(|j| Variety::try_parse(j, frost.as_ref())).collect::<Result<Vec<_>, _>>()?;
         params.varieties.insert(0, crate::variety::Variety::empty());
         
+        params.channels = match value_json_obj.get("channels") {
+            Some(channels_json_obj) => {
+                let mut channels = as_array(channels_json_obj)?.iter().map(|j| Channel::try_parse(j, &params)).collect::<Result<Vec<_>, _>>()?;
+                crate::channel::share_variety_demand(&mut channels, &params);
+                channels
+            },
+            None => vec![Channel::default_for(params.varieties.len())]
+        };
+        if params.channels.is_empty() {
+            bail!("Invalid channels: there must be at least one");
+        }
+
         let beds_json_array = as_array(&value_json_obj["beds"])?;
-        params.beds = beds_json_array.iter().map(|j| Bed::try_from(j)).collect::<Result<Vec<_>, _>>()?;
+        // The position in params.json of the bed that each bed or section belongs to
+        let mut listed_beds = vec![];
+        for (i, bed_json) in beds_json_array.iter().enumerate() {
+            let sections = Bed::try_parse_sections(bed_json, params.standard_bed_area)?;
+            listed_beds.extend(std::iter::repeat_n(i, sections.len()));
+            params.beds.extend(sections);
+        }
 
         match value_json_obj.get("planting_schedule_prior_year") {
             Some(planting_schedule_prior_year_json_obj) => {
-                params.plan_previous_year = Plan::try_from(planting_schedule_prior_year_json_obj)?;
+                let plan = Plan::try_from(planting_schedule_prior_year_json_obj)?;
+                params.plan_previous_year = fit_to_beds(plan, &listed_beds, "planting_schedule_prior_year")?;
             },
             None => params.plan_previous_year = Plan::new(params.beds.len())
         }
-        
+
+        if let Some(plans_json_obj) = value_json_obj.get("planting_schedules_earlier_years") {
+            params.plans_earlier_years = as_array(plans_json_obj)?
+                .iter()
+                .map(|j| fit_to_beds(Plan::try_from(j)?, &listed_beds, "planting_schedules_earlier_years"))
+                .collect::<Result<Vec<_>, _>>()?;
+        }
+
+        if let Some(rotation_json_obj) = value_json_obj.get("rotation") {
+            params.rotation = Rotation::try_parse(rotation_json_obj)?;
+        }
+
+        if let Some(succession_json_obj) = value_json_obj.get("succession") {
+            params.succession = SuccessionRules::try_parse(succession_json_obj)?;
+        }
+
+        if let Some(labour_json_obj) = value_json_obj.get("labour") {
+            params.labour = Some(LabourCapacity::try_parse(labour_json_obj)?);
+        }
+
+        if let Some(nursery_json_obj) = value_json_obj.get("nursery") {
+            let nursery = Nursery::try_parse(nursery_json_obj)?;
+            for variety in params.varieties.iter() {
+                if let Some(propagation) = &variety.propagation {
+                    if nursery.get_tray(&propagation.tray).is_none() {
+                        bail!("Invalid nursery: no capacity for {} trays of {}", propagation.tray, variety.name);
+                    }
+                }
+            }
+            params.nursery = Some(nursery);
+        }
+
+        if let Some(turnaround_json_obj) = value_json_obj.get("turnaround") {
+            params.turnaround = Turnaround::try_parse(turnaround_json_obj)?;
+        }
+
+        if let Some(risk_json_obj) = value_json_obj.get("risk") {
+            params.risk = Some(RiskObjective::try_parse(risk_json_obj, params.beds.len())?);
+        }
+
+        if let Some(solver_json_obj) = value_json_obj.get("solver") {
+            params.solver = SolverParams::try_from(solver_json_obj)?;
+        }
+
+        if let Some(pins_json_obj) = value_json_obj.get("pins") {
+            params.pins = as_array(pins_json_obj)?.iter().map(|j| Pin::try_parse(j, &params)).collect::<Result<Vec<_>, _>>()?;
+        }
+        params.locks = Locks::try_from_pins(&params.pins, &params)?;
+        params.actuals = Actuals::new(params.beds.len());
 
         Ok(params)
     }
 }
 
 impl Params {
+    // Add pins given in the same form as the pins section, such as those from the command line
+    pub fn add_pins(&mut self, pins_json: &[JsonValue]) -> Result<(), Box<dyn Error>> {
+        for pin_json in pins_json.iter() {
+            let pin = Pin::try_parse(pin_json, self)?;
+            self.pins.push(pin);
+        }
+        self.locks = Locks::try_from_pins(&self.pins, self)?;
+        Ok(())
+    }
+
     pub fn get_bed(&self, name: &str) -> Option<usize> {
         self.beds.iter().position(|b| b.name == name)
     }
+
+    // The bed with the given name, or all of its sections if it is split into sections
+    pub fn get_beds_or_sections(&self, name: &str) -> Vec<usize> {
+        (0..self.beds.len())
+            .filter(|&b| self.beds[b].name == name || self.beds[b].section_of.as_deref() == Some(name))
+            .collect()
+    }
+
+    pub fn get_variety(&self, name: &str) -> Option<usize> {
+        self.varieties.iter().position(|v| v.name == name)
+    }
+
+    // The number of units of a variety that can be sold in a week through all channels
+    pub fn get_demand(&self, variety: usize, week: usize) -> i32 {
+        self.channels.iter().map(|c| c.get_demand(variety, week, self)).sum()
+    }
+
+    // The demand for a variety in a week where a channel doesn't give its own, which is
+    // num_baskets unless the variety has its own demand schedule
+    pub fn get_variety_demand(&self, variety: usize, week: usize) -> i32 {
+        match self.varieties[variety].demand.get(week % SEASON_LENGTH) {
+            Some(&demand) => demand,
+            None => self.num_baskets
+        }
+    }
+
+    // What a harvest schedule is multiplied by for the bed. Beds without an area
+    // are of the standard size
+    pub fn get_yield_scale(&self, bed: usize) -> f64 {
+        match self.beds[bed].area {
+            Some(area) => area / self.standard_bed_area,
+            None => 1.0
+        }
+    }
+
+    pub fn is_pinned(&self, bed: usize, week: usize) -> bool {
+        self.pins.iter().any(|p| p.bed == bed && p.week == week && p.variety != 0)
+    }
+
+    // Lock the weeks of the plan before from_week, see Locks::freeze_before
+    pub fn freeze_before(&mut self, plan: &Plan, from_week: usize) {
+        let mut locks = self.locks.clone();
+        locks.freeze_before(plan, from_week, self);
+        self.locks = locks;
+    }
+
+    #[cfg(test)]
+    pub fn dummy(beds: Vec<Bed>, varieties: Vec<Variety>) -> Self {
+        let num_beds = beds.len();
+        let num_varieties = varieties.len();
+        Params{
+            beds,
+            varieties,
+            num_baskets: 120,
+            standard_bed_area: DEFAULT_STANDARD_BED_AREA,
+            channels: vec![Channel::default_for(num_varieties)],
+            plan_previous_year: Plan::new(num_beds),
+            plans_earlier_years: vec![],
+            rotation: Rotation::default(),
+            succession: SuccessionRules::default(),
+            labour: None,
+            nursery: None,
+            turnaround: Turnaround::default(),
+            risk: None,
+            solver: SolverParams::default(),
+            pins: vec![],
+            locks: Locks::new(num_beds),
+            actuals: Actuals::new(num_beds)
+        }
+    }
+}
+
+// Check that a plan of an earlier season is for the beds in params.json. A plan made before
+// some of the beds were divided into sections has the beds as listed, and each section of such
+// a bed takes on what was planted in the whole bed
+fn fit_to_beds(plan: Plan, listed_beds: &[usize], name: &str) -> Result<Plan, Box<dyn Error>> {
+    let num_listed_beds = listed_beds.last().map_or(0, |&b| b + 1);
+    match plan.get_num_beds() {
+        Some(n) if n == listed_beds.len() => Ok(plan),
+        Some(n) if n == num_listed_beds => Ok(plan.split_sections(listed_beds)),
+        _ => bail!("Invalid {}: must have {} weeks for each of the {} beds", name, SEASON_LENGTH, num_listed_beds)
+    }
+}
+
+// One bed and one variety, var-1, that yields 50 units in each of the third and fourth weeks
+// after it is planted, and a plan that plants it in week 10
+#[cfg(test)]
+pub fn test_params_and_plan() -> (Params, Plan) {
+    let mut params = Params::dummy(
+        vec![ Bed::dummy("bed-0") ],
+        vec![ Variety::empty(), Variety::dummy("var-1", vec![]) ]
+    );
+    params.varieties[1].harvest_schedule = vec![0, 0, 50, 50];
+    let mut plan = Plan::new(1);
+    plan.get_data_mut()[10] = 1;
+    (params, plan)
 }
 
 #[cfg(test)]
@@ -94,7 +324,15 @@ fn params_from_json() {
             "value_per_unit": 100
         }
     ],
-    "num_baskets": 42
+    "num_baskets": 42,
+    "solver": {
+        "population_size": 40,
+        "elitism": 2
+    },
+    "pins": [
+        { "bed": "~b01", "week": 12, "variety": "tomato" },
+        { "bed": "~b00", "week": 30, "until": 40 }
+    ]
 }"#).expect("test is wrong");
 
     let params = Params::try_from(&js).expect("failed to parse");
@@ -114,9 +352,45 @@ fn params_from_json() {
     assert_eq!(params.varieties[2].instructions["0"], "Transplant <variety> from pots labelled <label> into bed <bed>");
 
     assert_eq!(params.num_baskets, 42);
+    assert_eq!(params.solver.population_size, 40);
+    assert_eq!(params.solver.elitism, 2);
+    assert_eq!(params.pins.len(), 2);
+    assert!(params.is_pinned(1, 12));
+    assert!(!params.is_pinned(0, 30));
+}
+
+#[cfg(test)]
+#[test]
+fn prior_year_plan_fits_sections() {
+    let mut js = json::parse(r#"
+{
+    "beds": [ { "name": "~b00", "sections": 2 }, { "name": "~b01" } ],
+    "varieties": [ { "name": "lettuce", "requirements": [], "harvest_schedule": "0:3,4", "planting_schedule": "10-20", "instructions": {}, "value_per_unit": 100 } ],
+    "num_baskets": 42
+}"#).expect("test is wrong");
+
+    // Made before ~b00 was divided into sections
+    let mut plan = Plan::new(2);
+    plan.get_data_mut()[10] = 1;
+    plan.get_data_mut()[SEASON_LENGTH+12] = 1;
+    js["planting_schedule_prior_year"] = plan.to_json();
+    js["planting_schedules_earlier_years"] = json::array![ plan.to_json() ];
+    let params = Params::try_from(&js).expect("failed to parse");
+    assert_eq!(params.plan_previous_year.get_num_beds(), Some(3));
+    assert_eq!(params.plan_previous_year.get_last_planting_week(0), Some(10));
+    assert_eq!(params.plan_previous_year.get_last_planting_week(1), Some(10));
+    assert_eq!(params.plan_previous_year.get_last_planting_week(2), Some(12));
+    assert_eq!(params.plans_earlier_years[0].get_num_beds(), Some(3));
+
+    // Made with the sections
+    js["planting_schedule_prior_year"] = Plan::new(3).to_json();
+    Params::try_from(&js).expect("failed to parse");
+
+    js["planting_schedule_prior_year"] = Plan::new(1).to_json();
+    assert!(Params::try_from(&js).is_err());
 }
 
-pub const DEFAULT_PARAMS_JSON: &'static str = r#"{
+pub const DEFAULT_PARAMS_JSON: &str = r#"{
     "beds": [
         {
             "name": "~bA11",
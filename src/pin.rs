@@ -0,0 +1,239 @@
+use crate::params::Params;
+use crate::plan::Plan;
+use crate::bed_plan::BedPlan;
+use crate::common::*;
+use crate::constant::{SEASON_LENGTH, GeneId, VarietyId, WeekId};
+use std::error::Error;
+use json::JsonValue;
+use simple_error::*;
+
+// A planting that the solver must respect, as declared in the "pins" section of params.json
+// Either a variety that must be planted in a bed in a given week, or a range of weeks
+// during which a bed must be kept empty (variety 0)
+#[derive(Clone, Debug)]
+pub struct Pin {
+    pub bed: usize,
+    pub week: WeekId,
+    pub until: WeekId,
+    pub variety: VarietyId,
+}
+
+impl Pin {
+    // Parse a pin, resolving bed and variety names against the already loaded beds and varieties
+    pub fn try_parse(value: &JsonValue, params: &Params) -> Result<Self, Box<dyn Error>> {
+        let value_obj = as_object(value)?;
+
+        let bed_name = as_string(&value_obj["bed"])?;
+        let bed = match params.get_bed(&bed_name) {
+            Some(bed) => bed,
+            None => bail!("Invalid pin: unknown bed {}", bed_name)
+        };
+
+        let week = as_usize(&value_obj["week"])?;
+        let until = match value_obj.get("until") {
+            Some(until) => as_usize(until)?,
+            None => week
+        };
+        if week >= SEASON_LENGTH || until >= SEASON_LENGTH || until < week {
+            bail!("Invalid pin: bad weeks for bed {}", bed_name);
+        }
+
+        let variety = match value_obj.get("variety") {
+            Some(variety_json) => {
+                let variety_name = as_string(variety_json)?;
+                match params.get_variety(&variety_name) {
+                    Some(variety) if variety != 0 => variety,
+                    _ => bail!("Invalid pin: unknown variety {}", variety_name)
+                }
+            },
+            None => 0
+        };
+        if variety != 0 && until != week {
+            bail!("Invalid pin: a planting in bed {} can only be pinned to a single week", bed_name);
+        }
+
+        Ok(Pin {
+            bed,
+            week,
+            until,
+            variety
+        })
+    }
+}
+
+// The constraints on the genes of a Genome that follow from the pins
+// Pinned genes have a fixed value. Genes following a pinned planting are fixed to 0 for
+// the life of the crop so that it isn't replaced early. Bed weeks that must be kept empty
+// have their genes fixed to 0 and no crop may be growing in them either
+#[derive(Clone, Debug)]
+pub struct Locks {
+    fixed: Vec<Option<VarietyId>>,
+    fixed_genes: Vec<GeneId>,
+    keep_empty: Vec<bool>,
+    beds_with_empty_weeks: Vec<usize>,
+}
+
+impl Locks {
+    pub fn new(num_beds: usize) -> Self {
+        Locks {
+            fixed: vec![None; num_beds*SEASON_LENGTH],
+            fixed_genes: vec![],
+            keep_empty: vec![false; num_beds*SEASON_LENGTH],
+            beds_with_empty_weeks: vec![],
+        }
+    }
+
+    pub fn try_from_pins(pins: &[Pin], params: &Params) -> Result<Self, Box<dyn Error>> {
+        let mut locks = Locks::new(params.beds.len());
+
+        for pin in pins.iter().filter(|p| p.variety == 0) {
+            for week in pin.week..=pin.until {
+                locks.keep_empty(pin.bed, week);
+            }
+        }
+
+        for pin in pins.iter().filter(|p| p.variety != 0) {
+            let end_week = std::cmp::min(SEASON_LENGTH, pin.week + params.varieties[pin.variety].get_longevity());
+            for week in pin.week..end_week {
+                let gene = pin.bed*SEASON_LENGTH+week;
+                if locks.keep_empty[gene] {
+                    bail!("Invalid pin: {} in bed {} in week {} would grow into weeks that are pinned empty",
+                        params.varieties[pin.variety].name, params.beds[pin.bed].name, pin.week);
+                }
+                let value = if week == pin.week { pin.variety } else { 0 };
+                if locks.fixed[gene].is_some_and(|v| v != value) {
+                    bail!("Invalid pin: {} in bed {} in week {} conflicts with another pin",
+                        params.varieties[pin.variety].name, params.beds[pin.bed].name, pin.week);
+                }
+                locks.fix(gene, value);
+            }
+        }
+
+        Ok(locks)
+    }
+
+    // Require the bed to be empty in the given week
+    pub fn keep_empty(&mut self, bed: usize, week: WeekId) {
+        let gene = bed*SEASON_LENGTH+week;
+        self.fix(gene, 0);
+        self.keep_empty[gene] = true;
+        if !self.beds_with_empty_weeks.contains(&bed) {
+            self.beds_with_empty_weeks.push(bed);
+        }
+    }
+
+    // Keep the plan as it is before from_week, including any crops that are still in
+    // the ground in from_week, so that only the rest of the season can be re-planned
+    pub fn freeze_before(&mut self, plan: &Plan, from_week: WeekId, params: &Params) {
+        for bed in 0..params.beds.len() {
+            for week in 0..from_week {
+                self.fix(bed*SEASON_LENGTH+week, plan.get(bed, week));
+            }
+
+            let bed_plan = BedPlan::new(bed, plan, params);
+            for bed_week in bed_plan.iter().take(SEASON_LENGTH).skip(from_week) {
+                let planted_before = bed_week.crop_age > bed_week.week - from_week;
+                if bed_week.crop != 0 && planted_before {
+                    self.fix(bed*SEASON_LENGTH+bed_week.week, 0);
+                }
+            }
+        }
+    }
+
+    fn fix(&mut self, gene: GeneId, value: VarietyId) {
+        if self.fixed[gene].is_none() {
+            self.fixed_genes.push(gene);
+        }
+        self.fixed[gene] = Some(value);
+    }
+
+    pub fn is_locked(&self, gene: GeneId) -> bool {
+        self.fixed[gene].is_some()
+    }
+
+    // Modify the genes of a plan so that they satisfy the locks
+    pub fn apply(&self, genes: &mut [VarietyId], params: &Params) {
+        for &gene in self.fixed_genes.iter() {
+            genes[gene] = self.fixed[gene].unwrap();
+        }
+
+        // Drop any planting that would still be growing in a week that must be kept empty.
+        // Dropping a planting may let an earlier crop grow on, so keep going until there are none
+        for &bed in self.beds_with_empty_weeks.iter() {
+            while let Some(gene) = self.find_planting_in_empty_week(genes, bed, params) {
+                genes[gene] = 0;
+            }
+        }
+    }
+
+    fn find_planting_in_empty_week(&self, genes: &[VarietyId], bed: usize, params: &Params) -> Option<GeneId> {
+        let mut planting: Option<GeneId> = None;
+        let mut end_week = 0;
+        for week in 0..SEASON_LENGTH {
+            let gene = bed*SEASON_LENGTH+week;
+            if genes[gene] != 0 {
+                planting = Some(gene);
+                end_week = week + params.varieties[genes[gene]].get_longevity();
+            }
+            if self.keep_empty[gene] && week < end_week {
+                if let Some(planting) = planting.filter(|&p| !self.is_locked(p)) {
+                    return Some(planting);
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn locks_apply() {
+    let (params, _) = crate::params::test_params_and_plan();
+
+    let pins = vec![
+        Pin{ bed: 0, week: 10, until: 10, variety: 1 },
+        Pin{ bed: 0, week: 20, until: 25, variety: 0 },
+    ];
+    let locks = Locks::try_from_pins(&pins, &params).expect("invalid pins");
+
+    let mut genes = vec![1; SEASON_LENGTH];
+    locks.apply(&mut genes, &params);
+
+    assert_eq!(genes[10], 1);
+    assert_eq!(genes[11..14], [0, 0, 0]);
+    // week 17 would still be growing in week 20 so must go, as must the earlier ones it kept at bay
+    assert_eq!(genes[16], 1);
+    assert_eq!(genes[17..26], [0; 9]);
+    assert_eq!(genes[26], 1);
+
+    let pins = vec![
+        Pin{ bed: 0, week: 19, until: 19, variety: 1 },
+        Pin{ bed: 0, week: 20, until: 25, variety: 0 },
+    ];
+    Locks::try_from_pins(&pins, &params).expect_err("planting grows into empty weeks");
+}
+
+#[cfg(test)]
+#[test]
+fn locks_freeze_before() {
+    let (params, _) = crate::params::test_params_and_plan();
+
+    let mut plan = Plan::new(1);
+    plan.get_data_mut()[5] = 1;
+    plan.get_data_mut()[18] = 1;
+    plan.get_data_mut()[30] = 1;
+    let mut locks = Locks::new(1);
+    locks.freeze_before(&plan, 20, &params);
+
+    let mut genes = vec![0; SEASON_LENGTH];
+    genes[20] = 1;
+    genes[22] = 1;
+    locks.apply(&mut genes, &params);
+
+    assert_eq!(genes[5], 1);
+    assert_eq!(genes[18], 1);
+    // the crop planted in week 18 is still in the ground until week 22
+    assert_eq!(genes[20..22], [0, 0]);
+    assert_eq!(genes[22], 1);
+    assert_eq!(genes[30], 0);
+}
@@ -5,6 +5,7 @@ use std::convert::TryFrom;
 use json::JsonValue;
 use crate::constant::SEASON_LENGTH;
 use crate::constant::VarietyId;
+use crate::params::Params;
 
 #[derive(Clone, Debug)]
 pub struct Plan {
@@ -34,6 +35,21 @@ impl Plan {
         json::from(self.data.clone())
     }
 
+    // The number of beds the plan is for, or None if it isn't a whole number of seasons long
+    pub fn get_num_beds(&self) -> Option<usize> {
+        match self.data.len() % SEASON_LENGTH {
+            0 => Some(self.data.len() / SEASON_LENGTH),
+            _ => None
+        }
+    }
+
+    // Get a plan with a bed for each entry of bed_of, planted like the given bed of this plan
+    pub fn split_sections(&self, bed_of: &[usize]) -> Plan {
+        Plan {
+            data: bed_of.iter().flat_map(|&b| self.data[b*SEASON_LENGTH..(b+1)*SEASON_LENGTH].iter().copied()).collect()
+        }
+    }
+
     pub fn get_last_planting_week(&self, bed: usize) -> Option<usize> {
         self.data[bed*SEASON_LENGTH..bed*SEASON_LENGTH+SEASON_LENGTH].iter().rposition(|&x| x != 0)
     }
@@ -41,6 +57,49 @@ impl Plan {
     pub fn get_num_plantings(&self) -> i32 {
         self.data.iter().map(|x| match x { 0 => 0i32, _ => 1i32 }).sum()
     }
+
+    // Map a plan made for a different set of beds and varieties onto the given parameters,
+    // matching beds and varieties by name. Plantings in beds or of varieties that no longer
+    // exist, or that are no longer allowed in that bed in that week, are dropped. When the
+    // names the plan was made with aren't known, the indices are assumed not to have changed
+    pub fn repair(&self, bed_names: &[String], variety_names: &[String], params: &Params) -> Plan {
+        let mut result = Plan::new(params.beds.len());
+        let num_old_beds = self.data.len() / SEASON_LENGTH;
+
+        for (bed, bed_def) in params.beds.iter().enumerate() {
+            let old_bed = match bed_names.is_empty() {
+                true => Some(bed),
+                false => bed_names.iter().position(|name| *name == bed_def.name)
+            };
+            let old_bed = match old_bed {
+                Some(old_bed) if old_bed < num_old_beds => old_bed,
+                _ => continue
+            };
+
+            for week in 0..SEASON_LENGTH {
+                let old_variety = self.get(old_bed, week);
+                if old_variety == 0 {
+                    continue;
+                }
+
+                let variety = match variety_names.is_empty() {
+                    true => Some(old_variety).filter(|&v| v < params.varieties.len()),
+                    false => variety_names
+                        .get(old_variety)
+                        .and_then(|name| params.varieties.iter().position(|v| v.name == *name))
+                };
+
+                if let Some(variety) = variety {
+                    let def = &params.varieties[variety];
+                    if variety != 0 && def.planting_schedule[week] && def.are_requirements_met(bed_def) {
+                        result.data[bed*SEASON_LENGTH+week] = variety;
+                    }
+                }
+            }
+        }
+
+        result
+    }
 }
 
 impl TryFrom<&JsonValue> for Plan {
@@ -53,4 +112,33 @@ impl TryFrom<&JsonValue> for Plan {
             data: usize_arr
         })
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+#[test]
+fn repair_matches_names() {
+    let params = Params::dummy(
+        vec![
+            crate::bed::Bed::dummy("bed-new"),
+            crate::bed::Bed::dummy("bed-1"),
+        ],
+        vec![
+            crate::variety::Variety::empty(),
+            crate::variety::Variety::dummy("var-2", vec![]),
+            crate::variety::Variety::dummy("var-3", vec!["polytunnel"]),
+        ]
+    );
+
+    // Made with beds bed-0, bed-1 and varieties var-1, var-2, var-3
+    let mut old = Plan::new(2);
+    old.data[0] = 1;
+    old.data[SEASON_LENGTH] = 1;
+    old.data[SEASON_LENGTH+1] = 2;
+    old.data[SEASON_LENGTH+2] = 3;
+    let bed_names = vec![ "bed-0".to_string(), "bed-1".to_string() ];
+    let variety_names = vec![ "".to_string(), "var-1".to_string(), "var-2".to_string(), "var-3".to_string() ];
+
+    let repaired = old.repair(&bed_names, &variety_names, &params);
+
+    assert_eq!(repaired.get_num_plantings(), 1);
+    assert_eq!(repaired.get(1, 1), 1);
+}
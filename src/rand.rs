@@ -1,80 +1,115 @@
-use rand::rngs::ThreadRng;
+use rand::SeedableRng;
+use rand_chacha::ChaChaRng;
 use rand::distributions::Distribution;
 use rand::distributions::Uniform;
 use rand::distributions::WeightedIndex;
 use rand::distributions::Bernoulli;
 use crate::params::Params;
-use crate::constant::{POPULATION_SIZE, SEASON_LENGTH, GeneId, VarietyId, SolutionId};
-
-#[cfg(test)]
-use crate::plan::Plan;
+use crate::constant::{SEASON_LENGTH, GeneId, VarietyId, SolutionId};
+use std::error::Error;
 
 // Provides randomization methods for the evolutionary algorithm
+// All randomness is drawn from a single seeded generator so that a run can be reproduced
 pub struct Rand {
-    rng: ThreadRng,
+    seed: u64,
+    rng: ChaChaRng,
     dist_gene: Uniform<GeneId>,
-    dist_selection: WeightedIndex<SolutionId>,
+    dist_selection: WeightedIndex<f64>,
     dist_parent: Bernoulli,
+    dist_mutation: Bernoulli,
     plantable_varieties_by_week_by_bed: Vec<Vec<Vec<VarietyId>>>,
     dist_plantable_variety_by_week_by_bed: Vec<Vec<Option<Uniform<VarietyId>>>>,
 }
 
 impl Rand {
-    pub fn new(params: &Params) -> Rand {
-        let weights = 1..(POPULATION_SIZE+1);
+    #[allow(clippy::needless_range_loop)]
+    pub fn new(params: &Params, seed: u64) -> Result<Rand, Box<dyn Error>> {
+        // Rank based selection, the population is sorted so that the fittest individual is last
+        // The ranks are scaled so that the fittest has weight 1 and high pressures can't overflow
+        let population_size = params.solver.population_size as f64;
+        let weights = (1..=params.solver.population_size)
+            .map(|rank| (rank as f64 / population_size).powf(params.solver.selection_pressure));
 
         let mut plantable_varieties_by_week_by_bed = Vec::<Vec<Vec<VarietyId>>>::new();
         let mut dist_plantable_variety_by_week_by_bed = Vec::<Vec<Option<Uniform<VarietyId>>>>::new();
 
         for bed in 0..params.beds.len() {
-            let mut plantable_varieties_by_week = std::iter::repeat(vec![ ])
-                .take(SEASON_LENGTH)
+            let mut plantable_varieties_by_week = std::iter::repeat_n(vec![ ], SEASON_LENGTH)
                 .collect::<Vec<_>>();
 
             for week in 0..SEASON_LENGTH {
                 for variety in 0..params.varieties.len() {
-                    if params.varieties[variety].planting_schedule[week] {
-
-                        if params.varieties[variety].are_requirements_met(&params.beds[bed]) {
-                            plantable_varieties_by_week[week].push(variety);
-                        }
+                    if params.varieties[variety].planting_schedule[week]
+                        && params.varieties[variety].are_requirements_met(&params.beds[bed]) {
+                        plantable_varieties_by_week[week].push(variety);
                     }
                 }
             }
 
             let dist_plantable_variety_by_week = plantable_varieties_by_week
                 .iter()
-                .map(|w| if w.len() == 0 { None } else { Some(Uniform::from(0..w.len())) })
+                .map(|w| if w.is_empty() { None } else { Some(Uniform::from(0..w.len())) })
                 .collect::<Vec<_>>();
 
             plantable_varieties_by_week_by_bed.push(plantable_varieties_by_week);
             dist_plantable_variety_by_week_by_bed.push(dist_plantable_variety_by_week);
         }
 
-        return Rand{
-            rng: rand::thread_rng(),
+        let mut rng = ChaChaRng::seed_from_u64(seed);
+        // Fill the generator's buffer so that its position in the stream is always well defined
+        rng.set_word_pos(0);
+
+        Ok(Rand{
+            seed,
+            rng,
             dist_gene: Uniform::from(0..params.beds.len()*SEASON_LENGTH),
-            dist_selection: WeightedIndex::new(weights).unwrap(),
+            dist_selection: WeightedIndex::new(weights)?,
             dist_parent: Bernoulli::new(0.5).unwrap(),
-            plantable_varieties_by_week_by_bed: plantable_varieties_by_week_by_bed,
-            dist_plantable_variety_by_week_by_bed: dist_plantable_variety_by_week_by_bed
-        }
+            dist_mutation: Bernoulli::new(params.solver.mutation_rate)
+                .map_err(|_| "Invalid solver settings: mutation_rate must be between 0 and 1")?,
+            plantable_varieties_by_week_by_bed,
+            dist_plantable_variety_by_week_by_bed
+        })
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    // Get how far through its stream of random numbers the generator is,
+    // so that the state can be restored from a checkpoint
+    pub fn get_word_pos(&self) -> u128 {
+        self.rng.get_word_pos()
+    }
+
+    pub fn set_word_pos(&mut self, word_pos: u128) {
+        self.rng.set_word_pos(word_pos)
+    }
+
+    // Choose a seed for a new run when the user hasn't asked for a specific one
+    pub fn random_seed() -> u64 {
+        rand::random()
     }
 
     // Choose one of two parents with equal probability
     pub fn random_parent(&mut self) -> bool {
-        return self.dist_parent.sample(&mut self.rng);
+        self.dist_parent.sample(&mut self.rng)
+    }
+
+    // Decide whether a newly spawned child should be mutated
+    pub fn random_mutation(&mut self) -> bool {
+        self.dist_mutation.sample(&mut self.rng)
     }
 
     // Choose a gene from the genome at random with uniform probability
     pub fn random_gene(&mut self) -> GeneId {
-        return self.dist_gene.sample(&mut self.rng);
+        self.dist_gene.sample(&mut self.rng)
     }
 
     // Choose an individual from the fitness sorted population, with
-    // likelihood of selection proportional to fitness
+    // likelihood of selection increasing with rank according to the selection pressure
     pub fn select_individual(&mut self) -> SolutionId {
-        return self.dist_selection.sample(&mut self.rng);
+        self.dist_selection.sample(&mut self.rng)
     }
 
     // Choose a variety from the set of varieties that can be planted in
@@ -93,22 +128,17 @@ impl Rand {
 #[cfg(test)]
 #[test]
 fn random_variety_meets_requirements() {
-    let params = Params{
-        beds: vec![ crate::bed::Bed{
-            name: "bed-0".to_string(),
-            properties: vec![ "rq-1".to_string() ]
-        } ],
-        varieties: vec![ 
+    let params = Params::dummy(
+        vec![ crate::bed::Bed{ properties: vec![ "rq-1".to_string() ], ..crate::bed::Bed::dummy("bed-0") } ],
+        vec![ 
             crate::variety::Variety::dummy("var-0", vec![]),
             crate::variety::Variety::dummy("var-1", vec!["rq-1"]),
             crate::variety::Variety::dummy("var-2", vec!["rq-2"]),
             crate::variety::Variety::dummy("var-2", vec!["rq-1", "rq-2"]),
-        ],
-        num_baskets: 120,
-        plan_previous_year: Plan::new(1)
-    };
+        ]
+    );
 
-    let mut subject = Rand::new(&params);
+    let mut subject = Rand::new(&params, 0).expect("failed to create");
 
     for _ in 0..100 {
         for w in 0..SEASON_LENGTH {
@@ -122,21 +152,16 @@ fn random_variety_meets_requirements() {
 #[cfg(test)]
 #[test]
 fn random_variety_satisfies_planting_schedule() {
-    let mut params = Params{
-        beds: vec![ crate::bed::Bed{
-            name: "bed-0".to_string(),
-            properties: vec![ ]
-        } ],
-        varieties: vec![ crate::variety::Variety::dummy("var-0", vec![]) ],
-        num_baskets: 120,
-        plan_previous_year: Plan::new(1)
-    };
+    let mut params = Params::dummy(
+        vec![ crate::bed::Bed::dummy("bed-0") ],
+        vec![ crate::variety::Variety::dummy("var-0", vec![]) ]
+    );
 
     for i in 0..SEASON_LENGTH {
         params.varieties[0].planting_schedule[i] = i % 2 == 0;
     }
 
-    let mut subject = Rand::new(&params);
+    let mut subject = Rand::new(&params, 0).expect("failed to create");
 
     for _ in 0..100 {
         for w in 0..SEASON_LENGTH {
@@ -153,28 +178,66 @@ fn random_variety_satisfies_planting_schedule() {
 #[cfg(test)]
 #[test]
 fn select_individual() {
-    let params = Params{
-        beds: vec![ crate::bed::Bed{
-            name: "bed-1".to_string(),
-            properties: vec![]
-        } ],
-        varieties: vec![ crate::variety::Variety::dummy("var-1", vec![]) ],
-        num_baskets: 120,
-        plan_previous_year: Plan::new(1)
-    };
-    let mut subject = Rand::new(&params);
+    let mut params = Params::dummy(
+        vec![ crate::bed::Bed::dummy("bed-1") ],
+        vec![ crate::variety::Variety::dummy("var-1", vec![]) ]
+    );
+    let mut subject = Rand::new(&params, 0).expect("failed to create");
 
     let mut count_low = 0;
     let mut count_hi = 0;
     for _ in 0..1000 {
         let i = subject.select_individual();
-        assert!(i < POPULATION_SIZE);
+        assert!(i < params.solver.population_size);
         if i < 10 {
             count_low += 1
         }
-        if i > POPULATION_SIZE-10 {
+        if i > params.solver.population_size-10 {
             count_hi += 1
         }
     }
-    assert!(count_low < count_hi)
-}
\ No newline at end of file
+    assert!(count_low < count_hi);
+
+    // Pressures high enough to overflow the weights of the ranks select only the fittest
+    params.solver.selection_pressure = 1000.0;
+    let mut subject = Rand::new(&params, 0).expect("failed to create");
+    assert_eq!(subject.select_individual(), params.solver.population_size - 1);
+}
+
+#[cfg(test)]
+#[test]
+fn same_seed_same_sequence() {
+    let params = Params::dummy(
+        vec![ crate::bed::Bed::dummy("bed-1") ],
+        vec![ crate::variety::Variety::dummy("var-1", vec![]), crate::variety::Variety::dummy("var-2", vec![]) ]
+    );
+    let mut a = Rand::new(&params, 42).expect("failed to create");
+    let mut b = Rand::new(&params, 42).expect("failed to create");
+    let mut c = Rand::new(&params, 43).expect("failed to create");
+
+    let seq_a = (0..100).map(|_| a.random_gene()).collect::<Vec<_>>();
+    let seq_b = (0..100).map(|_| b.random_gene()).collect::<Vec<_>>();
+    let seq_c = (0..100).map(|_| c.random_gene()).collect::<Vec<_>>();
+    assert_eq!(seq_a, seq_b);
+    assert_ne!(seq_a, seq_c);
+}
+
+#[cfg(test)]
+#[test]
+fn restore_word_pos() {
+    let params = Params::dummy(
+        vec![ crate::bed::Bed::dummy("bed-1") ],
+        vec![ crate::variety::Variety::dummy("var-1", vec![]) ]
+    );
+    let mut a = Rand::new(&params, 42).expect("failed to create");
+    for _ in 0..37 {
+        a.random_gene();
+    }
+
+    let mut b = Rand::new(&params, a.get_seed()).expect("failed to create");
+    b.set_word_pos(a.get_word_pos());
+
+    let seq_a = (0..100).map(|_| a.random_gene()).collect::<Vec<_>>();
+    let seq_b = (0..100).map(|_| b.random_gene()).collect::<Vec<_>>();
+    assert_eq!(seq_a, seq_b);
+}
@@ -5,6 +5,10 @@ use crate::common::*;
 use simple_error::*;
 use json::object;
 use crate::params::Params;
+use crate::checkpoint::Checkpoint;
+use crate::revision::Revision;
+use crate::events::{Event, Actuals};
+use crate::constant::WeekId;
 use std::convert::{TryFrom};
 
 // Represents the state of the application, which is stored on disk
@@ -12,16 +16,25 @@ use std::convert::{TryFrom};
 pub struct Repo {
     path: std::path::PathBuf,
     params_hash: std::string::String,
-    plan: Option<Plan>
+    current: Option<Revision>,
+    // Earlier plans that have been replaced, oldest first
+    revisions: Vec<Revision>,
+    // What actually happened in the beds, in the order it was logged
+    events: Vec<Event>,
+    // Pins added with 'harvest pin', in the same form as the pins section of params.json
+    pins: Vec<json::JsonValue>
 }
 
 impl Repo {
 
-    pub fn new(path: &std::path::PathBuf) -> Self {
+    pub fn new(path: &std::path::Path) -> Self {
         let mut repo = Repo {
             path: path.to_path_buf(),
             params_hash: std::string::String::new(),
-            plan: None
+            current: None,
+            revisions: vec![],
+            events: vec![],
+            pins: vec![]
         };
         repo.path.push(".harvest");
         repo
@@ -57,6 +70,16 @@ impl Repo {
         let params_old_str = std::fs::read_to_string(params_old_path)?;
         let mut params_old_json = json::parse(&params_old_str)?;
         let plan_old = repo_old.require_plan()?;
+
+        // The old prior year becomes the most recent of the earlier years, kept for crop rotation
+        let mut plans_earlier_years = json::JsonValue::new_array();
+        if !params_old_json["planting_schedule_prior_year"].is_null() {
+            plans_earlier_years.push(params_old_json["planting_schedule_prior_year"].clone())?;
+        }
+        for plan_json in params_old_json["planting_schedules_earlier_years"].members() {
+            plans_earlier_years.push(plan_json.clone())?;
+        }
+        params_old_json["planting_schedules_earlier_years"] = plans_earlier_years;
         params_old_json["planting_schedule_prior_year"] = plan_old.to_json();
 
         fs::write(self.get_params_path(), params_old_json.dump().as_bytes())?;
@@ -66,9 +89,11 @@ impl Repo {
         Ok(())
     }
 
-    // Drop the current solution
-    pub fn reset(&mut self) {
-        self.plan = None;
+    // Drop the current solution and its earlier revisions
+    pub fn reset(&mut self) -> Result<(), Box<dyn Error>> {
+        self.current = None;
+        self.revisions = vec![];
+        self.clear_checkpoint()
     }
 
     // Load application state from the repo in the current directory
@@ -78,12 +103,20 @@ impl Repo {
         let repo_str = std::fs::read_to_string(self.get_repo_path())?;
         let repo_json = json::parse(&repo_str)?;
         let params_hash = as_string(&repo_json["params_sha1"])?;
-        self.params_hash = String::from(params_hash);
+        self.params_hash = params_hash;
         if !repo_json["plan"].is_null() {
-            let plan = Plan::try_from(&repo_json["plan"])?;
-            self.plan = Some(plan);
+            self.current = Some(Revision::try_from(&repo_json)?);
+        }
+        if !repo_json["revisions"].is_null() {
+            self.revisions = as_array(&repo_json["revisions"])?.iter().map(Revision::try_from).collect::<Result<Vec<_>, _>>()?;
         }
-        
+        if !repo_json["events"].is_null() {
+            self.events = as_array(&repo_json["events"])?.iter().map(Event::try_from).collect::<Result<Vec<_>, _>>()?;
+        }
+        if !repo_json["pins"].is_null() {
+            self.pins = as_array(&repo_json["pins"])?.clone();
+        }
+
         Ok(())
     }
 
@@ -91,28 +124,53 @@ impl Repo {
     pub fn save(&self) -> Result<(), Box<dyn Error>> {
         self.require_initialized()?;
 
-        let json = object!{
-            "params_sha1" => self.params_hash.clone(),
-            "plan" => match &self.plan { Some(p) => p.to_json(), None => json::Null },
+        // The current revision is stored at the top level, earlier ones under "revisions"
+        let mut json = match &self.current {
+            Some(revision) => revision.to_json(),
+            None => object!{ "plan" => json::Null }
         };
+        json["params_sha1"] = self.params_hash.clone().into();
+        json["revisions"] = self.revisions.iter().map(|r| r.to_json()).collect::<Vec<_>>().into();
+        json["events"] = self.events.iter().map(|e| e.to_json()).collect::<Vec<_>>().into();
+        json["pins"] = self.pins.clone().into();
 
         fs::write(self.get_repo_path(), json.dump().as_bytes())?;
 
         Ok(())
     }
 
-    pub fn put_solution(&mut self, plan: Plan)-> Result<(), Box<dyn Error>> {
-        self.plan = Some(plan);
+    // Store a new plan along with the seed and crate version that produced it,
+    // so that the plan can be regenerated exactly. The plan it replaces is kept as a revision
+    pub fn put_solution(&mut self, plan: Plan, seed: u64, from_week: Option<WeekId>, params: &Params)-> Result<(), Box<dyn Error>> {
+        if let Some(previous) = self.current.take() {
+            self.revisions.push(previous);
+        }
+        self.current = Some(Revision::new(plan, seed, from_week, params));
         self.params_hash = self.get_params_hash()?;
         Ok(())
     }
 
+    // Replace the current plan with a new one, without keeping it as an earlier revision
+    pub fn replace_solution(&mut self, plan: Plan, seed: u64, from_week: Option<WeekId>, params: &Params)-> Result<(), Box<dyn Error>> {
+        self.current = None;
+        self.put_solution(plan, seed, from_week, params)
+    }
+
+    pub fn get_current_revision(&self) -> Option<&Revision> {
+        self.current.as_ref()
+    }
+
+    // The revision number of the current plan, counting from 1
+    pub fn get_revision_number(&self) -> usize {
+        self.revisions.len() + 1
+    }
+
     pub fn require_plan(&self) -> Result<&Plan, Box<dyn Error>> {
         self.require_initialized()?;
-        match &self.plan {
-            Some(p) => {
+        match &self.current {
+            Some(Revision { plan: p, .. }) => {
                 match self.is_params_unchanged() {
-                    Ok(true) => Ok(&p),
+                    Ok(true) => Ok(p),
                     Ok(false) => bail!("The parameters have changed and the solution must be regenerated. Try 'harvest plan'"),
                     Err(e) => Err(e)
                 }
@@ -121,9 +179,19 @@ impl Repo {
         }
     }
 
+    // Get the stored plan even if the parameters have changed since it was made,
+    // repaired to fit the current beds and varieties
+    pub fn require_current_plan_for(&self, params: &Params) -> Result<Plan, Box<dyn Error>> {
+        self.require_initialized()?;
+        match &self.current {
+            Some(r) => Ok(r.plan.repair(&r.beds, &r.varieties, params)),
+            None => bail!("The is no solution. Try 'harvest plan'")
+        }
+    }
+
     pub fn require_no_solution(&self) -> Result<(), Box<dyn Error>> {
         self.require_initialized()?;
-        match &self.plan {
+        match &self.current {
             Some(_) => {
                 match self.is_params_unchanged() {
                     Ok(true) => bail!("Already solved. Try 'harvest reset'"),
@@ -135,10 +203,68 @@ impl Repo {
         }
     }
 
+    // Write a checkpoint of a solver run, so that the run can be resumed if it is interrupted
+    pub fn put_checkpoint(&self, checkpoint: &Checkpoint) -> Result<(), Box<dyn Error>> {
+        self.require_initialized()?;
+        let mut json = checkpoint.to_json();
+        json["params_sha1"] = self.get_params_hash()?.into();
+        fs::write(self.get_checkpoint_path(), json.dump().as_bytes())?;
+        Ok(())
+    }
+
+    // Get the checkpoint of the last solver run, provided the parameters haven't changed since
+    pub fn require_checkpoint(&self) -> Result<Checkpoint, Box<dyn Error>> {
+        self.require_initialized()?;
+        if !self.get_checkpoint_path().exists() {
+            bail!("There is no run to resume. Try 'harvest plan'");
+        }
+
+        let checkpoint_str = std::fs::read_to_string(self.get_checkpoint_path())?;
+        let checkpoint_json = json::parse(&checkpoint_str)?;
+        if as_string(&checkpoint_json["params_sha1"])? != self.get_params_hash()? {
+            bail!("The parameters have changed since the run was interrupted and it can't be resumed. Try 'harvest plan'");
+        }
+
+        Checkpoint::try_from(&checkpoint_json)
+    }
+
+    pub fn clear_checkpoint(&self) -> Result<(), Box<dyn Error>> {
+        if self.get_checkpoint_path().exists() {
+            fs::remove_file(self.get_checkpoint_path())?;
+        }
+        Ok(())
+    }
+
+    // Add a pin, provided the parameters are still valid with it. Pins are kept when the plan
+    // is reset or replaced, and change the parameters just as editing params.json does
+    pub fn put_pin(&mut self, pin: json::JsonValue) -> Result<(), Box<dyn Error>> {
+        let mut params = self.get_params()?;
+        params.add_pins(std::slice::from_ref(&pin))?;
+        self.pins.push(pin);
+        Ok(())
+    }
+
+    // Remove the pins added for the bed in the week, returning how many there were
+    pub fn remove_pins(&mut self, bed: &str, week: WeekId) -> usize {
+        let num_pins = self.pins.len();
+        self.pins.retain(|p| !(p["bed"] == bed && p["week"] == week));
+        num_pins - self.pins.len()
+    }
+
+    // Log something that actually happened. Events are kept when the plan is reset or replaced
+    pub fn put_event(&mut self, event: Event, params: &Params) -> Result<(), Box<dyn Error>> {
+        event.validate(params)?;
+        self.events.push(event);
+        Ok(())
+    }
+
+    // Get the parameters, along with the logged events resolved against them
     pub fn get_params(&self) -> Result<Params, Box<dyn Error>> {
         let params_str = std::fs::read_to_string(self.get_params_path())?;
         let params_json = json::parse(&params_str)?;
-        let params = Params::try_from(&params_json)?;
+        let mut params = Params::try_from(&params_json)?;
+        params.add_pins(&self.pins)?;
+        params.actuals = Actuals::from_events(&self.events, &params);
         Ok(params)
     }
 
@@ -159,12 +285,25 @@ impl Repo {
         result
     }
 
+    fn get_checkpoint_path(&self) -> std::path::PathBuf {
+        let mut result = self.path.to_path_buf();
+        result.push("checkpoint.json");
+        result
+    }
+
     fn is_initialized(&self) -> bool {
         self.path.exists()
     }
 
     fn get_params_hash(&self) ->  Result<std::string::String, std::io::Error> {
-        sha256_digest(&self.get_params_path())
+        let params_hash = sha256_digest(&self.get_params_path())?;
+        if self.pins.is_empty() {
+            return Ok(params_hash);
+        }
+        let mut hasher = sha1::Sha1::new();
+        hasher.update(params_hash.as_ref());
+        hasher.update(json::stringify(self.pins.clone()).as_ref());
+        Ok(hasher.digest().to_string())
     }
 
     fn require_initialized(&self) -> Result<(), Box<dyn Error>> {
@@ -185,3 +324,44 @@ fn repo_init() {
     subject.init().expect("init failed");
     subject.init().expect_err("double init");
 }
+
+#[cfg(test)]
+#[test]
+fn repo_stores_seed() {
+    let mut dir = std::env::temp_dir();
+    dir.push(format!("harvest-test-seed-{}", chrono::Utc::now().timestamp_nanos()));
+    let mut subject = Repo::new(&dir);
+    subject.init().expect("init failed");
+    let params = Params::dummy(vec![], vec![]);
+    subject.put_solution(Plan::new(0), 1234, None, &params).expect("put failed");
+    subject.save().expect("save failed");
+
+    let mut loaded = Repo::new(&dir);
+    loaded.load().expect("load failed");
+    let revision = loaded.get_current_revision().expect("no revision");
+    assert_eq!(revision.seed, Some(1234));
+    assert_eq!(revision.version.as_deref(), Some(env!("CARGO_PKG_VERSION")));
+}
+
+#[cfg(test)]
+#[test]
+fn repo_keeps_revisions() {
+    let mut dir = std::env::temp_dir();
+    dir.push(format!("harvest-test-revisions-{}", chrono::Utc::now().timestamp_nanos()));
+    let mut subject = Repo::new(&dir);
+    subject.init().expect("init failed");
+    let params = Params::dummy(vec![], vec![]);
+    subject.put_solution(Plan::new(0), 1, None, &params).expect("put failed");
+    subject.put_solution(Plan::new(0), 2, Some(20), &params).expect("put failed");
+    subject.save().expect("save failed");
+
+    let mut loaded = Repo::new(&dir);
+    loaded.load().expect("load failed");
+    assert_eq!(loaded.get_revision_number(), 2);
+    let revision = loaded.get_current_revision().expect("no revision");
+    assert_eq!(revision.seed, Some(2));
+    assert_eq!(revision.from_week, Some(20));
+
+    loaded.reset().expect("reset failed");
+    assert_eq!(loaded.get_revision_number(), 1);
+}
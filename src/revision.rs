@@ -0,0 +1,76 @@
+use crate::plan::Plan;
+use crate::params::Params;
+use crate::common::*;
+use crate::constant::WeekId;
+use std::error::Error;
+use std::convert::TryFrom;
+use json::{JsonValue, object};
+
+// A plan as stored by the Repo, along with what is needed to regenerate it
+// and to match its indices up with the beds and varieties it was made for
+#[derive(Clone, Debug)]
+pub struct Revision {
+    pub plan: Plan,
+    pub seed: Option<u64>,
+    pub version: Option<String>,
+    pub beds: Vec<String>,
+    pub varieties: Vec<String>,
+    // The week from which the plan was re-designed, if it was made by replanning
+    pub from_week: Option<WeekId>,
+}
+
+impl Revision {
+    pub fn new(plan: Plan, seed: u64, from_week: Option<WeekId>, params: &Params) -> Self {
+        Revision {
+            plan,
+            seed: Some(seed),
+            version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            beds: params.beds.iter().map(|b| b.name.clone()).collect(),
+            varieties: params.varieties.iter().map(|v| v.name.clone()).collect(),
+            from_week,
+        }
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        object!{
+            "plan" => self.plan.to_json(),
+            "seed" => match self.seed { Some(s) => s.into(), None => json::Null },
+            "version" => match &self.version { Some(v) => v.clone().into(), None => json::Null },
+            "plan_beds" => self.beds.clone(),
+            "plan_varieties" => self.varieties.clone(),
+            "from_week" => match self.from_week { Some(w) => w.into(), None => json::Null },
+        }
+    }
+}
+
+impl TryFrom<&JsonValue> for Revision {
+    type Error = Box<dyn Error>;
+
+    // Everything but the plan is optional, as older repos only stored the plan
+    fn try_from(value: &JsonValue) -> Result<Self, Self::Error> {
+        let mut revision = Revision {
+            plan: Plan::try_from(&value["plan"])?,
+            seed: None,
+            version: None,
+            beds: vec![],
+            varieties: vec![],
+            from_week: None,
+        };
+        if !value["seed"].is_null() {
+            revision.seed = Some(as_u64(&value["seed"])?);
+        }
+        if !value["version"].is_null() {
+            revision.version = Some(as_string(&value["version"])?);
+        }
+        if !value["plan_beds"].is_null() {
+            revision.beds = as_array(&value["plan_beds"])?.iter().map(as_string).collect::<Result<Vec<_>, _>>()?;
+        }
+        if !value["plan_varieties"].is_null() {
+            revision.varieties = as_array(&value["plan_varieties"])?.iter().map(as_string).collect::<Result<Vec<_>, _>>()?;
+        }
+        if !value["from_week"].is_null() {
+            revision.from_week = Some(as_usize(&value["from_week"])?);
+        }
+        Ok(revision)
+    }
+}
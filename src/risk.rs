@@ -0,0 +1,142 @@
+use crate::common::*;
+use crate::constant::SEASON_LENGTH;
+use crate::variety::Variety;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaChaRng;
+use std::error::Error;
+use json::JsonValue;
+use simple_error::*;
+
+// Plantings are made from the start of last season, for crops carried over, to the end of next
+const FIRST_PLANTING_WEEK: i32 = -(SEASON_LENGTH as i32);
+const PLANTING_WEEKS: usize = SEASON_LENGTH * 3;
+
+// How far the yield of a variety can stray from its harvest_schedule, loaded from its "risk"
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct YieldRisk {
+    // Standard deviation of the yield of a planting, as a fraction of its harvest_schedule
+    pub deviation: f64,
+    // Chance that a planting fails and yields nothing at all
+    pub failure_probability: f64,
+}
+
+impl YieldRisk {
+    pub fn try_parse(value: &JsonValue) -> Result<Self, Box<dyn Error>> {
+        let value_obj = as_object(value)?;
+        let mut risk = YieldRisk::default();
+        if let Some(deviation) = value_obj.get("yield_deviation") {
+            risk.deviation = as_f64(deviation)?;
+        }
+        if let Some(failure_probability) = value_obj.get("failure_probability") {
+            risk.failure_probability = as_f64(failure_probability)?;
+        }
+        if risk.deviation < 0.0 || !(0.0..=1.0).contains(&risk.failure_probability) {
+            bail!("Invalid risk: yield_deviation must not be negative and failure_probability must be between 0 and 1");
+        }
+        Ok(risk)
+    }
+}
+
+// One simulated season: a random draw for each bed and planting week, from which the
+// yield of whatever is planted there is worked out. The same draws apply to any plan,
+// so that plans are compared under the same luck
+#[derive(Clone, Debug)]
+pub struct Outcome {
+    // A uniform number deciding failure, and a standard normal one deciding the yield
+    draws: Vec<(f64, f64)>,
+}
+
+impl Outcome {
+    pub fn generate(num_beds: usize, rng: &mut ChaChaRng) -> Self {
+        let draws = (0..num_beds*PLANTING_WEEKS)
+            .map(|_| {
+                // Box-Muller transform
+                let u1: f64 = 1.0 - rng.gen::<f64>();
+                let u2: f64 = rng.gen();
+                let normal = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+                (rng.gen(), normal)
+            })
+            .collect();
+        Outcome {
+            draws,
+        }
+    }
+
+    // What the harvest_schedule of a planting is multiplied by in this outcome
+    pub fn get_yield_factor(&self, bed: usize, planting_week: i32, variety: &Variety) -> f64 {
+        let slot = (planting_week - FIRST_PLANTING_WEEK).clamp(0, PLANTING_WEEKS as i32 - 1) as usize;
+        let (uniform, normal) = self.draws[bed*PLANTING_WEEKS + slot];
+        if uniform < variety.risk.failure_probability {
+            return 0.0;
+        }
+        (1.0 + variety.risk.deviation * normal).max(0.0)
+    }
+}
+
+// Lets the solver trade profit for certainty, loaded from the "risk" section of params.json
+// The profit of a plan is taken to be its mean over the simulated outcomes, less aversion
+// times their standard deviation
+#[derive(Clone, Debug)]
+pub struct RiskObjective {
+    pub aversion: f64,
+    pub outcomes: Vec<Outcome>,
+}
+
+impl RiskObjective {
+    pub fn try_parse(value: &JsonValue, num_beds: usize) -> Result<Self, Box<dyn Error>> {
+        let value_obj = as_object(value)?;
+        let simulations = match value_obj.get("simulations") {
+            Some(simulations) => as_usize(simulations)?,
+            None => 20
+        };
+        if simulations == 0 {
+            bail!("Invalid risk: there must be at least one simulation");
+        }
+        let aversion = match value_obj.get("aversion") {
+            Some(aversion) => as_f64(aversion)?,
+            None => 1.0
+        };
+        let seed = match value_obj.get("seed") {
+            Some(seed) => as_u64(seed)?,
+            None => 0
+        };
+
+        let mut rng = ChaChaRng::seed_from_u64(seed);
+        Ok(RiskObjective {
+            aversion,
+            outcomes: (0..simulations).map(|_| Outcome::generate(num_beds, &mut rng)).collect(),
+        })
+    }
+
+    pub fn adjust(&self, profits: &[i32]) -> i32 {
+        let (mean, deviation) = get_mean_and_deviation(profits);
+        (mean - self.aversion * deviation).round() as i32
+    }
+}
+
+pub fn get_mean_and_deviation(values: &[i32]) -> (f64, f64) {
+    let n = values.len() as f64;
+    let mean = values.iter().map(|&v| v as f64).sum::<f64>() / n;
+    let variance = values.iter().map(|&v| (v as f64 - mean).powi(2)).sum::<f64>() / n;
+    (mean, variance.sqrt())
+}
+
+#[cfg(test)]
+#[test]
+fn outcome_yield_factors() {
+    let mut variety = Variety::dummy("var-1", vec![]);
+    let mut rng = ChaChaRng::seed_from_u64(1);
+    let outcome = Outcome::generate(1, &mut rng);
+    assert_eq!(outcome.get_yield_factor(0, 10, &variety), 1.0);
+
+    variety.risk = YieldRisk{ deviation: 0.0, failure_probability: 1.0 };
+    assert_eq!(outcome.get_yield_factor(0, 10, &variety), 0.0);
+
+    variety.risk = YieldRisk{ deviation: 0.2, failure_probability: 0.0 };
+    let factors = (FIRST_PLANTING_WEEK..PLANTING_WEEKS as i32 + FIRST_PLANTING_WEEK)
+        .map(|week| (outcome.get_yield_factor(0, week, &variety) * 1000.0) as i32)
+        .collect::<Vec<_>>();
+    let (mean, deviation) = get_mean_and_deviation(&factors);
+    assert!((mean - 1000.0).abs() < 50.0);
+    assert!((deviation - 200.0).abs() < 50.0);
+}
@@ -0,0 +1,119 @@
+use crate::plan::Plan;
+use crate::params::Params;
+use crate::evaluator::Evaluator;
+use crate::risk::{Outcome, get_mean_and_deviation};
+use crate::constant::SEASON_LENGTH;
+use rand::SeedableRng;
+use rand_chacha::ChaChaRng;
+use core::fmt::Display;
+
+// The profit of a plan, and how often it misses the demand, over many simulated seasons
+// in which yields vary as the varieties' risks say
+pub struct RiskReport<'a> {
+    params: &'a Params,
+    // Profit of each simulated season
+    profits: Vec<i32>,
+    // Number of simulated seasons that missed the demand for each variety in each week
+    shortfalls: Vec<Vec<usize>>,
+}
+
+impl<'a> RiskReport<'a> {
+    pub fn simulate(params: &'a Params, plan: &Plan, simulations: usize, seed: u64) -> RiskReport<'a> {
+        let mut rng = ChaChaRng::seed_from_u64(seed);
+        let mut profits = vec![];
+        let mut shortfalls = vec![vec![0; SEASON_LENGTH]; params.varieties.len()];
+
+        for _ in 0..simulations {
+            let outcome = Outcome::generate(params.beds.len(), &mut rng);
+            let evaluator = Evaluator::with_outcome(params, plan, &outcome);
+            profits.push(evaluator.get_profit());
+
+            // Missing the demand means that less was harvested or in store than could be
+            // sold, as for the market saturation
+            let harvest_plan = evaluator.get_harvest_plan();
+            let stock_plan = evaluator.get_stock_plan();
+            for variety in 1..params.varieties.len() {
+                for week in 0..SEASON_LENGTH {
+                    let demand = params.get_demand(variety, week);
+                    let is_harvestable = params.varieties[variety].is_harvestable_in_week(week);
+                    if is_harvestable && harvest_plan[variety][week] + stock_plan[variety][week] < demand {
+                        shortfalls[variety][week] += 1;
+                    }
+                }
+            }
+        }
+
+        RiskReport {
+            params,
+            profits,
+            shortfalls,
+        }
+    }
+
+    pub fn get_expected_profit(&self) -> f64 {
+        get_mean_and_deviation(&self.profits).0
+    }
+
+    // The profit that the given fraction of the simulated seasons fall below
+    pub fn get_percentile(&self, fraction: f64) -> i32 {
+        let mut profits = self.profits.clone();
+        profits.sort_unstable();
+        let index = (fraction * (profits.len() - 1) as f64).round() as usize;
+        profits[index]
+    }
+
+    pub fn get_shortfall_probability(&self, variety: usize, week: usize) -> f64 {
+        self.shortfalls[variety][week] as f64 / self.profits.len() as f64
+    }
+}
+
+impl<'a> Display for RiskReport<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let varieties = (1..self.params.varieties.len())
+            .filter(|&v| !self.params.varieties[v].is_cover_crop())
+            .collect::<Vec<_>>();
+
+        writeln!(f, "Chance of missing the demand\n")?;
+        write!(f, "{:>11}", "Week")?;
+        for &v in varieties.iter() {
+            let mut name = self.params.varieties[v].name.clone();
+            name.truncate(9);
+            write!(f, "{:>11}", name)?;
+        }
+        writeln!(f)?;
+        for week in 0..SEASON_LENGTH {
+            write!(f, "{:>11}", week)?;
+            for &v in varieties.iter() {
+                match self.params.varieties[v].is_harvestable_in_week(week) {
+                    true => write!(f, "{:>10.0}%", self.get_shortfall_probability(v, week) * 100.0)?,
+                    false => write!(f, "           ")?
+                }
+            }
+            writeln!(f)?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "Simulations: {}", self.profits.len())?;
+        writeln!(f, "Expected profit: {:.2}", self.get_expected_profit() / 100.0)?;
+        writeln!(f, "5th percentile: {:.2}", self.get_percentile(0.05) as f32 / 100.0)?;
+        writeln!(f, "95th percentile: {:.2}", self.get_percentile(0.95) as f32 / 100.0)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn risk_report_simulates_failures() {
+    use crate::risk::YieldRisk;
+
+    let (mut params, plan) = crate::params::test_params_and_plan();
+
+    let certain = RiskReport::simulate(&params, &plan, 10, 1);
+    assert_eq!(certain.get_percentile(0.05), Evaluator::new(&params, &plan).get_profit());
+    assert_eq!(certain.get_shortfall_probability(1, 12), 1.0);
+
+    params.varieties[1].risk = YieldRisk{ deviation: 0.0, failure_probability: 1.0 };
+    params.num_baskets = 0;
+    let failing = RiskReport::simulate(&params, &plan, 10, 1);
+    assert_eq!(failing.get_percentile(0.95), -1);
+    assert_eq!(failing.get_shortfall_probability(1, 12), 0.0);
+}
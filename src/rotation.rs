@@ -0,0 +1,157 @@
+use crate::plan::Plan;
+use crate::params::Params;
+use crate::common::*;
+use crate::constant::{SEASON_LENGTH, VarietyId, WeekRange};
+use std::collections::HashMap;
+use std::error::Error;
+use json::JsonValue;
+use simple_error::*;
+
+// A planting that follows another of the same family in the same bed too soon
+// Weeks before this season are negative, e.g. week 40 of last season is -12
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Violation {
+    pub bed: usize,
+    pub week: i32,
+    pub variety: VarietyId,
+    pub previous_week: i32,
+    pub previous_variety: VarietyId,
+}
+
+// Crop rotation rules, loaded from the "rotation" section of params.json
+// Each family has a minimum number of weeks between two plantings of that family in
+// the same bed, counting plantings in earlier seasons. With a penalty, each violation
+// is subtracted from the fitness, without one the solver isn't allowed to make them
+#[derive(Clone, Debug, Default)]
+pub struct Rotation {
+    pub gaps: HashMap<String, WeekRange>,
+    pub penalty: Option<i32>,
+}
+
+impl Rotation {
+    pub fn try_parse(value: &JsonValue) -> Result<Self, Box<dyn Error>> {
+        let value_obj = as_object(value)?;
+        let mut rotation = Rotation::default();
+        for (family, gap) in as_object(&value_obj["families"])?.iter() {
+            rotation.gaps.insert(family.to_string(), as_usize(gap)?);
+        }
+        if let Some(penalty) = value_obj.get("penalty") {
+            let penalty = as_int(penalty)?;
+            if penalty < 0 {
+                bail!("Invalid rotation: penalty must not be negative");
+            }
+            rotation.penalty = Some(penalty);
+        }
+        Ok(rotation)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.gaps.is_empty()
+    }
+
+    // Whether violations must be removed from plans rather than penalised
+    pub fn is_forbidding(&self) -> bool {
+        !self.is_empty() && self.penalty.is_none()
+    }
+
+    pub fn find_violations(&self, plan: &Plan, params: &Params) -> Vec<Violation> {
+        let mut violations = vec![];
+        if self.is_empty() {
+            return violations;
+        }
+
+        for bed in 0..params.beds.len() {
+            let mut previous = self.get_history(bed, params);
+            for week in 0..SEASON_LENGTH {
+                let variety = plan.get(bed, week);
+                if variety == 0 {
+                    continue;
+                }
+                if let Some(violation) = self.find_conflict(&previous, bed, week as i32, variety, params) {
+                    violations.push(violation);
+                }
+                previous.push((week as i32, variety));
+            }
+        }
+        violations
+    }
+
+    // Drop plantings that break the rotation from the genes of a plan, unless they are locked
+    pub fn enforce(&self, genes: &mut [VarietyId], params: &Params) {
+        for bed in 0..params.beds.len() {
+            let mut previous = self.get_history(bed, params);
+            for week in 0..SEASON_LENGTH {
+                let gene = bed*SEASON_LENGTH+week;
+                if genes[gene] == 0 {
+                    continue;
+                }
+                let conflict = self.find_conflict(&previous, bed, week as i32, genes[gene], params);
+                if conflict.is_some() && !params.locks.is_locked(gene) {
+                    genes[gene] = 0;
+                } else {
+                    previous.push((week as i32, genes[gene]));
+                }
+            }
+        }
+    }
+
+    // Plantings in the bed in earlier seasons, oldest first
+    fn get_history(&self, bed: usize, params: &Params) -> Vec<(i32, VarietyId)> {
+        let mut history = vec![];
+        let seasons = params.plans_earlier_years.iter().rev().chain(std::iter::once(&params.plan_previous_year));
+        let num_seasons = params.plans_earlier_years.len() + 1;
+        for (season, plan) in seasons.enumerate() {
+            if plan.get_data().len() < (bed+1)*SEASON_LENGTH {
+                continue;
+            }
+            let offset = ((num_seasons - season) * SEASON_LENGTH) as i32;
+            for week in 0..SEASON_LENGTH {
+                let variety = plan.get(bed, week);
+                if variety != 0 && variety < params.varieties.len() {
+                    history.push((week as i32 - offset, variety));
+                }
+            }
+        }
+        history
+    }
+
+    fn find_conflict(&self, previous: &[(i32, VarietyId)], bed: usize, week: i32, variety: VarietyId, params: &Params) -> Option<Violation> {
+        let family = params.varieties[variety].family.as_ref()?;
+        let gap = *self.gaps.get(family)? as i32;
+        let &(previous_week, previous_variety) = previous
+            .iter()
+            .rev()
+            .find(|(_, v)| params.varieties[*v].family.as_ref() == Some(family))?;
+        match week - previous_week < gap {
+            true => Some(Violation{ bed, week, variety, previous_week, previous_variety }),
+            false => None
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn rotation_violations() {
+    let mut params = Params::dummy(
+        vec![ crate::bed::Bed::dummy("bed-0") ],
+        vec![ crate::variety::Variety::empty(), crate::variety::Variety::dummy("var-1", vec![]), crate::variety::Variety::dummy("var-2", vec![]) ]
+    );
+    params.varieties[1].family = Some("Brassica".to_string());
+    params.varieties[2].family = Some("Brassica".to_string());
+    params.rotation.gaps.insert("Brassica".to_string(), 40);
+    params.plan_previous_year.get_data_mut()[30] = 2;
+
+    let mut plan = Plan::new(1);
+    plan.get_data_mut()[10] = 1;
+    plan.get_data_mut()[30] = 2;
+    let violations = params.rotation.find_violations(&plan, &params);
+    assert_eq!(violations, vec![
+        Violation{ bed: 0, week: 10, variety: 1, previous_week: -22, previous_variety: 2 },
+        Violation{ bed: 0, week: 30, variety: 2, previous_week: 10, previous_variety: 1 },
+    ]);
+
+    let mut genes = plan.get_data().to_vec();
+    params.rotation.enforce(&mut genes, &params);
+    assert_eq!(genes[10], 0);
+    assert_eq!(genes[30], 2);
+}
@@ -0,0 +1,230 @@
+use std::convert::TryFrom;
+use std::error::Error;
+use json::{JsonValue, object};
+use simple_error::*;
+use crate::common::*;
+use crate::constant::{DEFAULT_POPULATION_SIZE, DEFAULT_STAGNATION_LIMIT, DEFAULT_ISLANDS, DEFAULT_MIGRATION_INTERVAL, DEFAULT_CHECKPOINT_INTERVAL};
+
+// Settings of the evolutionary algorithm
+// Loaded from the optional "solver" section of params.json and can be overridden on the command line
+#[derive(Clone, Debug, PartialEq)]
+pub struct SolverParams {
+    // Number of individuals in the population
+    pub population_size: usize,
+    // Number of generations without improvement after which the solver stops
+    pub stagnation_limit: usize,
+    // Number of mutations applied to each mutated child
+    pub mutations: usize,
+    // Probability that a child is mutated at all
+    pub mutation_rate: f64,
+    // Number of best individuals copied unchanged into the next generation
+    pub elitism: usize,
+    // Exponent applied to the rank of an individual to get its selection weight.
+    // 0 selects parents uniformly, 1 is linear ranking, higher values favour the fittest more strongly
+    pub selection_pressure: f64,
+    // Stop after this many generations in total
+    pub max_generations: Option<usize>,
+    // Stop after this many seconds
+    pub max_time: Option<u64>,
+    // Stop once the best plan makes at least this much profit, in the same units as the printed profit
+    pub target_profit: Option<f64>,
+    // Number of populations evolved side by side
+    pub islands: usize,
+    // Number of threads to evolve the islands on, defaults to one per available CPU
+    pub threads: Option<usize>,
+    // Number of generations between migrations of individuals between islands
+    pub migration_interval: usize,
+    // Number of individuals that migrate from each island to the next
+    pub migration_size: usize,
+    // Number of generations between checkpoints of the run being written to disk
+    pub checkpoint_interval: usize,
+}
+
+impl Default for SolverParams {
+    fn default() -> Self {
+        SolverParams {
+            population_size: DEFAULT_POPULATION_SIZE,
+            stagnation_limit: DEFAULT_STAGNATION_LIMIT,
+            mutations: 1,
+            mutation_rate: 1.0,
+            elitism: 1,
+            selection_pressure: 1.0,
+            max_generations: None,
+            max_time: None,
+            target_profit: None,
+            islands: DEFAULT_ISLANDS,
+            threads: None,
+            migration_interval: DEFAULT_MIGRATION_INTERVAL,
+            migration_size: 1,
+            checkpoint_interval: DEFAULT_CHECKPOINT_INTERVAL,
+        }
+    }
+}
+
+impl SolverParams {
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if self.population_size < 2 {
+            bail!("Invalid solver settings: population_size must be at least 2");
+        }
+        if self.stagnation_limit == 0 {
+            bail!("Invalid solver settings: stagnation_limit must be at least 1");
+        }
+        if !(0.0..=1.0).contains(&self.mutation_rate) {
+            bail!("Invalid solver settings: mutation_rate must be between 0 and 1");
+        }
+        if self.elitism >= self.population_size {
+            bail!("Invalid solver settings: elitism must be less than population_size");
+        }
+        if !self.selection_pressure.is_finite() || self.selection_pressure < 0.0 {
+            bail!("Invalid solver settings: selection_pressure must not be negative");
+        }
+        if self.max_generations == Some(0) {
+            bail!("Invalid solver settings: max_generations must be at least 1");
+        }
+        if let Some(target_profit) = self.target_profit {
+            if !target_profit.is_finite() {
+                bail!("Invalid solver settings: target_profit must be a number");
+            }
+        }
+        if self.islands == 0 {
+            bail!("Invalid solver settings: islands must be at least 1");
+        }
+        if self.threads == Some(0) {
+            bail!("Invalid solver settings: threads must be at least 1");
+        }
+        if self.migration_interval == 0 {
+            bail!("Invalid solver settings: migration_interval must be at least 1");
+        }
+        if self.migration_size >= self.population_size {
+            bail!("Invalid solver settings: migration_size must be less than population_size");
+        }
+        if self.checkpoint_interval == 0 {
+            bail!("Invalid solver settings: checkpoint_interval must be at least 1");
+        }
+        Ok(())
+    }
+
+    // Whether a run with these settings evolves just like one with the other settings.
+    // The threads and the checkpoint interval don't change the outcome of a run
+    pub fn is_same_run(&self, other: &SolverParams) -> bool {
+        let mut other = other.clone();
+        other.threads = self.threads;
+        other.checkpoint_interval = self.checkpoint_interval;
+        *self == other
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let mut result = object!{
+            "population_size" => self.population_size,
+            "stagnation_limit" => self.stagnation_limit,
+            "mutations" => self.mutations,
+            "mutation_rate" => self.mutation_rate,
+            "elitism" => self.elitism,
+            "selection_pressure" => self.selection_pressure,
+            "islands" => self.islands,
+            "migration_interval" => self.migration_interval,
+            "migration_size" => self.migration_size,
+            "checkpoint_interval" => self.checkpoint_interval,
+        };
+        if let Some(max_generations) = self.max_generations {
+            result["max_generations"] = max_generations.into();
+        }
+        if let Some(max_time) = self.max_time {
+            result["max_time"] = max_time.into();
+        }
+        if let Some(target_profit) = self.target_profit {
+            result["target_profit"] = target_profit.into();
+        }
+        if let Some(threads) = self.threads {
+            result["threads"] = threads.into();
+        }
+        result
+    }
+
+    // Get the number of threads to evolve the islands on
+    pub fn get_threads(&self) -> usize {
+        let threads = match self.threads {
+            Some(threads) => threads,
+            None => std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+        };
+        std::cmp::min(threads, self.islands)
+    }
+}
+
+impl TryFrom<&JsonValue> for SolverParams {
+    type Error = Box<dyn Error>;
+
+    fn try_from(value: &JsonValue) -> Result<Self, Self::Error> {
+        let value_obj = as_object(value)?;
+        let mut result = SolverParams::default();
+
+        if let Some(v) = value_obj.get("population_size") {
+            result.population_size = as_usize(v)?;
+        }
+        if let Some(v) = value_obj.get("stagnation_limit") {
+            result.stagnation_limit = as_usize(v)?;
+        }
+        if let Some(v) = value_obj.get("mutations") {
+            result.mutations = as_usize(v)?;
+        }
+        if let Some(v) = value_obj.get("mutation_rate") {
+            result.mutation_rate = as_f64(v)?;
+        }
+        if let Some(v) = value_obj.get("elitism") {
+            result.elitism = as_usize(v)?;
+        }
+        if let Some(v) = value_obj.get("selection_pressure") {
+            result.selection_pressure = as_f64(v)?;
+        }
+        if let Some(v) = value_obj.get("max_generations") {
+            result.max_generations = Some(as_usize(v)?);
+        }
+        if let Some(v) = value_obj.get("max_time") {
+            result.max_time = Some(as_u64(v)?);
+        }
+        if let Some(v) = value_obj.get("target_profit") {
+            result.target_profit = Some(as_f64(v)?);
+        }
+        if let Some(v) = value_obj.get("islands") {
+            result.islands = as_usize(v)?;
+        }
+        if let Some(v) = value_obj.get("threads") {
+            result.threads = Some(as_usize(v)?);
+        }
+        if let Some(v) = value_obj.get("migration_interval") {
+            result.migration_interval = as_usize(v)?;
+        }
+        if let Some(v) = value_obj.get("migration_size") {
+            result.migration_size = as_usize(v)?;
+        }
+        if let Some(v) = value_obj.get("checkpoint_interval") {
+            result.checkpoint_interval = as_usize(v)?;
+        }
+
+        result.validate()?;
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn solver_params_from_json() {
+    let js = json::parse(r#"
+{
+    "population_size": 50,
+    "mutation_rate": 0.5,
+    "selection_pressure": 2
+}"#).expect("test is wrong");
+    let solver = SolverParams::try_from(&js).expect("failed to parse");
+    assert_eq!(solver.population_size, 50);
+    assert_eq!(solver.stagnation_limit, DEFAULT_STAGNATION_LIMIT);
+    assert_eq!(solver.mutation_rate, 0.5);
+    assert_eq!(solver.selection_pressure, 2.0);
+
+    let js = json::parse(r#"{ "population_size": 10, "elitism": 10 }"#).expect("test is wrong");
+    SolverParams::try_from(&js).expect_err("elitism too large");
+
+    let js = json::parse(r#"{ "mutation_rate": 1.5 }"#).expect("test is wrong");
+    SolverParams::try_from(&js).expect_err("mutation rate out of range");
+}
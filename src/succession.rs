@@ -0,0 +1,141 @@
+use crate::plan::Plan;
+use crate::params::Params;
+use crate::bed_plan::BedPlan;
+use crate::common::*;
+use crate::constant::{SEASON_LENGTH, VarietyId, WeekId};
+use std::error::Error;
+use json::JsonValue;
+use simple_error::*;
+
+// A planting and the crop that was in the bed before it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Succession {
+    pub bed: usize,
+    pub week: WeekId,
+    pub variety: VarietyId,
+    pub predecessor: VarietyId,
+}
+
+// How the avoid_after and prefer_after lists of the varieties are applied, loaded from the
+// optional "succession" section of params.json. With a penalty, each planting after a crop
+// it avoids is subtracted from the fitness, without one the solver isn't allowed to make
+// them. Each planting after a crop it prefers adds the bonus
+#[derive(Clone, Debug, Default)]
+pub struct SuccessionRules {
+    pub penalty: Option<i32>,
+    pub bonus: i32,
+}
+
+impl SuccessionRules {
+    pub fn try_parse(value: &JsonValue) -> Result<Self, Box<dyn Error>> {
+        let value_obj = as_object(value)?;
+        let mut rules = SuccessionRules::default();
+        if let Some(penalty) = value_obj.get("penalty") {
+            rules.penalty = Some(as_int(penalty)?);
+        }
+        if let Some(bonus) = value_obj.get("bonus") {
+            rules.bonus = as_int(bonus)?;
+        }
+        if rules.penalty.unwrap_or(0) < 0 || rules.bonus < 0 {
+            bail!("Invalid succession: penalty and bonus must not be negative");
+        }
+        Ok(rules)
+    }
+
+    // Whether plantings after a crop they avoid must be removed from plans rather than penalised
+    pub fn is_forbidding(&self, params: &Params) -> bool {
+        self.penalty.is_none() && params.varieties.iter().any(|v| !v.avoid_after.is_empty())
+    }
+
+    // Plantings in the season that follow a crop they should avoid
+    pub fn find_avoided(&self, plan: &Plan, params: &Params) -> Vec<Succession> {
+        (0..params.beds.len())
+            .flat_map(|bed| find_successions(bed, plan, params))
+            .filter(|s| params.varieties[s.variety].is_avoided_after(&params.varieties[s.predecessor]))
+            .collect()
+    }
+
+    // Plantings in the season that follow a crop they prefer
+    pub fn find_preferred(&self, plan: &Plan, params: &Params) -> Vec<Succession> {
+        (0..params.beds.len())
+            .flat_map(|bed| find_successions(bed, plan, params))
+            .filter(|s| params.varieties[s.variety].is_preferred_after(&params.varieties[s.predecessor]))
+            .collect()
+    }
+
+    // Drop plantings that follow a crop they avoid, unless they are locked. Dropping a planting
+    // makes the crop before it the predecessor of the next planting, so the plantings of each
+    // bed are checked in order in a single pass
+    pub fn enforce(&self, plan: &mut Plan, params: &Params) {
+        for bed in 0..params.beds.len() {
+            let mut last_crop = None;
+            for planting in find_plantings(bed, plan, params) {
+                let predecessor = last_crop.unwrap_or(planting.predecessor);
+                let gene = bed*SEASON_LENGTH+planting.week;
+                let is_avoided = predecessor != 0 && params.varieties[planting.variety].is_avoided_after(&params.varieties[predecessor]);
+                if is_avoided && plan.get_data()[gene] == planting.variety && !params.locks.is_locked(gene) {
+                    plan.get_data_mut()[gene] = 0;
+                } else {
+                    last_crop = Some(planting.variety);
+                }
+            }
+        }
+    }
+}
+
+// Every planting in the bed during the season, with the crop before it or 0
+fn find_plantings(bed: usize, plan: &Plan, params: &Params) -> Vec<Succession> {
+    BedPlan::new(bed, plan, params)
+        .iter()
+        .take(SEASON_LENGTH)
+        .filter(|w| w.crop != 0 && w.crop_age == 0)
+        .map(|w| Succession{ bed, week: w.week, variety: w.crop, predecessor: w.predecessor })
+        .collect()
+}
+
+// Every planting in the bed during the season that follows another crop
+fn find_successions(bed: usize, plan: &Plan, params: &Params) -> Vec<Succession> {
+    find_plantings(bed, plan, params)
+        .into_iter()
+        .filter(|s| s.predecessor != 0)
+        .collect()
+}
+
+#[cfg(test)]
+#[test]
+fn succession_avoided_and_preferred() {
+    let mut params = Params::dummy(
+        vec![ crate::bed::Bed::dummy("bed-0") ],
+        vec![ crate::variety::Variety::empty(), crate::variety::Variety::dummy("lettuce", vec![]), crate::variety::Variety::dummy("carrot", vec![]), crate::variety::Variety::dummy("bean", vec![]) ]
+    );
+    for variety in params.varieties.iter_mut().skip(1) {
+        variety.harvest_schedule = vec![0, 0, 50, 50];
+    }
+    params.varieties[2].avoid_after = vec!["lettuce".to_string()];
+    params.varieties[1].prefer_after = vec!["Legume".to_string()];
+    params.varieties[3].family = Some("Legume".to_string());
+
+    // bean, then lettuce after it, then carrot straight after the lettuce
+    let mut plan = Plan::new(1);
+    plan.get_data_mut()[2] = 3;
+    plan.get_data_mut()[10] = 1;
+    plan.get_data_mut()[20] = 2;
+    let rules = SuccessionRules::default();
+    assert_eq!(rules.find_avoided(&plan, &params), vec![ Succession{ bed: 0, week: 20, variety: 2, predecessor: 1 } ]);
+    assert_eq!(rules.find_preferred(&plan, &params), vec![ Succession{ bed: 0, week: 10, variety: 1, predecessor: 3 } ]);
+
+    assert!(rules.is_forbidding(&params));
+    rules.enforce(&mut plan, &params);
+    assert_eq!(plan.get(0, 20), 0);
+    assert_eq!(plan.get(0, 10), 1);
+
+    // with the carrot in week 20 dropped, the one in week 30 follows the lettuce too
+    plan.get_data_mut()[20] = 2;
+    plan.get_data_mut()[30] = 2;
+    plan.get_data_mut()[40] = 1;
+    rules.enforce(&mut plan, &params);
+    assert_eq!(plan.get(0, 20), 0);
+    assert_eq!(plan.get(0, 30), 0);
+    assert_eq!(plan.get(0, 40), 1);
+    assert!(rules.find_avoided(&plan, &params).is_empty());
+}
@@ -8,12 +8,12 @@ pub struct Tasks{
 impl Tasks {
     pub fn new() -> Self {
         Tasks{
-            content: std::iter::repeat(vec![]).take(SEASON_LENGTH).collect()
+            content: std::iter::repeat_n(vec![], SEASON_LENGTH).collect()
         }
     }
 
-    pub fn add(&mut self, week: usize, ins: &String) {
-        self.content[week].push(ins.clone())
+    pub fn add(&mut self, week: usize, ins: &str) {
+        self.content[week].push(ins.to_string())
     }
 
     pub fn get(&self, week: usize) -> &Vec<String> {
@@ -6,18 +6,106 @@ use std::collections::HashMap;
 use std::error::Error;
 use simple_error::*;
 use regex::Regex;
+use crate::labour::Labour;
+use crate::nursery::Propagation;
+use crate::risk::YieldRisk;
+use crate::frost::FrostDates;
+
+// The costs of growing a variety, in value units
+// Without any costs given, each planting costs one value unit, so that of two
+// otherwise equal plans the one with fewer plantings is preferred
+#[derive(Clone, Debug, PartialEq)]
+pub struct Costs {
+    // Seed or plants for one planting
+    pub seed: i32,
+    // Labour for one planting, including raising it in the nursery
+    pub planting: i32,
+    // Picking and packing each harvested unit
+    pub harvest_unit: i32,
+    // Each week the crop occupies a bed
+    pub bed_week: i32,
+}
+
+impl Default for Costs {
+    fn default() -> Self {
+        Costs {
+            seed: 0,
+            planting: 1,
+            harvest_unit: 0,
+            bed_week: 0,
+        }
+    }
+}
+
+impl Costs {
+    fn try_parse(value: &JsonValue) -> Result<Self, Box<dyn Error>> {
+        if value.is_null() {
+            return Ok(Costs::default());
+        }
+
+        // Once costs are given, those left out are free
+        let mut costs = Costs{ seed: 0, planting: 0, harvest_unit: 0, bed_week: 0 };
+        let value_obj = as_object(value)?;
+        if let Some(v) = value_obj.get("seed") {
+            costs.seed = as_int(v)?;
+        }
+        if let Some(v) = value_obj.get("planting") {
+            costs.planting = as_int(v)?;
+        }
+        if let Some(v) = value_obj.get("harvest_unit") {
+            costs.harvest_unit = as_int(v)?;
+        }
+        if let Some(v) = value_obj.get("bed_week") {
+            costs.bed_week = as_int(v)?;
+        }
+        Ok(costs)
+    }
+
+    // The one-off cost of a planting
+    pub fn per_planting(&self) -> i32 {
+        self.seed + self.planting
+    }
+}
 
 // Represents a variety of crop
 // Loaded from params.json and used as part of the input parameters to the plan generating algorithm
 #[derive(Clone)]
 pub struct Variety {
     pub name: String,
+    // Plant family, such as Brassica, used for crop rotation
+    pub family: Option<String>,
     pub planting_schedule: [ bool; SEASON_LENGTH ],
     pub harvest_schedule: Vec<HarvestableUnits>,
     harvestable_by_week: Vec<bool>,
     pub requirements: Vec<String>,
+    // Varieties or families that this variety shouldn't, or should preferably, be planted after
+    pub avoid_after: Vec<String>,
+    pub prefer_after: Vec<String>,
     pub instructions: HashMap<String, String>,
-    pub value_per_unit: i32
+    // Estimated time taken by the instructions, by the same keys
+    pub labour: HashMap<String, Labour>,
+    // How the variety is raised in the nursery, None if it is sown straight into the bed
+    pub propagation: Option<Propagation>,
+    // Weeks the bed must be left empty before planting the variety, if more than the
+    // turnaround of the bed. See Turnaround
+    pub turnaround: WeekRange,
+    // Value to the soil of each week the crop grows in a bed of the standard size, for
+    // cover crops and green manures. Counted in the fitness but not in the profit
+    pub soil_benefit: i32,
+    // How uncertain the yield is, for simulating harvests. See Outcome
+    pub risk: YieldRisk,
+    pub value_per_unit: i32,
+    // Price per unit in each week of the season, falling back on value_per_unit
+    // where empty or shorter than a season. See get_price
+    pub price_schedule: Vec<i32>,
+    pub costs: Costs,
+    // Number of weeks that unsold harvest keeps for, 0 if it must be sold fresh
+    pub storage_life: WeekRange,
+    // Fraction of the stored harvest lost each week
+    pub spoilage: f64,
+    // Units that can be sold in each week of the season. Empty, or shorter than
+    // a season, where the demand falls back on num_baskets. See Params::get_demand
+    pub demand: Vec<HarvestableUnits>
 }
 
 impl Variety {
@@ -25,17 +113,48 @@ impl Variety {
         self.requirements.iter().all(|r| bed.properties.contains(r))
     }
 
-    pub fn try_parse(value: &JsonValue) -> Result<Self, Box<dyn Error>> {
-        let value_obj = as_object(&value)?;
+    pub fn is_avoided_after(&self, predecessor: &Variety) -> bool {
+        self.avoid_after.iter().any(|n| predecessor.is_named(n))
+    }
+
+    pub fn is_preferred_after(&self, predecessor: &Variety) -> bool {
+        self.prefer_after.iter().any(|n| predecessor.is_named(n))
+    }
+
+    // Whether the name is the name of the variety or of its family
+    fn is_named(&self, name: &str) -> bool {
+        self.name == name || self.family.as_deref() == Some(name)
+    }
+
+    // Windows in the planting schedule relative to the frost dates are resolved into weeks,
+    // see FrostDates::resolve
+    #[allow(clippy::needless_range_loop)]
+    pub fn try_parse(value: &JsonValue, frost: Option<&FrostDates>) -> Result<Self, Box<dyn Error>> {
+        let value_obj = as_object(value)?;
         let name = as_string(&value_obj["name"])?;
 
+        let family = match &value_obj["family"] {
+            JsonValue::Null => None,
+            family_json => Some(as_string(family_json)?)
+        };
+
         let requirements = match &value_obj["requirements"] {
             JsonValue::Array(arr) => arr.iter().map(|p| as_string(p)).collect::<Result<Vec<_>,_>>(),
             JsonValue::Null => Ok(vec![]),
             _ => bail!("Invalid requirements")
         }?;
+        let avoid_after = match &value_obj["avoid_after"] {
+            JsonValue::Array(arr) => arr.iter().map(|p| as_string(p)).collect::<Result<Vec<_>,_>>(),
+            JsonValue::Null => Ok(vec![]),
+            _ => bail!("Invalid avoid_after for {}", name)
+        }?;
+        let prefer_after = match &value_obj["prefer_after"] {
+            JsonValue::Array(arr) => arr.iter().map(|p| as_string(p)).collect::<Result<Vec<_>,_>>(),
+            JsonValue::Null => Ok(vec![]),
+            _ => bail!("Invalid prefer_after for {}", name)
+        }?;
 
-        let planting_schedule_str = as_string(&value_obj["planting_schedule"])?;
+        let planting_schedule_str = FrostDates::resolve(frost, &as_string(&value_obj["planting_schedule"])?)?;
         let mut planting_schedule = [false; SEASON_LENGTH];
 
         lazy_static! {
@@ -60,30 +179,59 @@ impl Variety {
             }
         }
 
-        let mut harvest_schedule = vec![];
-        let harvest_schedule_str = as_string(&value_obj["harvest_schedule"])?;
-        lazy_static! {
-            static ref REGEX_HARVEST_SCHEDULE: Regex = Regex::new("([0-9]+)(:([0-9]+))?").unwrap();
-        }
-        for cap in REGEX_HARVEST_SCHEDULE.captures_iter(&harvest_schedule_str) {
-            match cap.get(3) {
-                None => {
-                    let harvestable_units = &cap[1].parse::<i32>()?;
-                    harvest_schedule.push(*harvestable_units);
-                },
-                Some(_) => {
-                    let harvestable_units = &cap[1].parse::<i32>()?;
-                    let run_length = &cap[3].parse::<i32>()?;
-                    for _ in 0..*run_length {
-                        harvest_schedule.push(*harvestable_units);
-                    }
-                }
-            }
+        let harvest_schedule = parse_run_lengths(&as_string(&value_obj["harvest_schedule"])?)?;
+
+        let demand = match &value_obj["demand"] {
+            JsonValue::Null => vec![],
+            demand_json => parse_run_lengths(&as_string(demand_json)?)?
+        };
+        if demand.len() > SEASON_LENGTH {
+            bail!("Invalid demand for {}: longer than a season", name);
         }
 
-        let instructions = try_parse_instructions(&value_obj["instructions"])?;
+        let (instructions, labour) = try_parse_instructions(&value_obj["instructions"])?;
+
+        let propagation = match &value_obj["propagation"] {
+            JsonValue::Null => None,
+            propagation_json => Some(Propagation::try_parse(propagation_json)?)
+        };
+
+        let turnaround = match &value_obj["turnaround"] {
+            JsonValue::Null => 0,
+            turnaround_json => as_usize(turnaround_json)?
+        };
+
+        let soil_benefit = match &value_obj["soil_benefit"] {
+            JsonValue::Null => 0,
+            soil_benefit_json => as_int(soil_benefit_json)?
+        };
+
+        let risk = match &value_obj["risk"] {
+            JsonValue::Null => YieldRisk::default(),
+            risk_json => YieldRisk::try_parse(risk_json)?
+        };
 
         let value_per_unit = as_int(&value_obj["value_per_unit"])?;
+        let price_schedule = match &value_obj["price_schedule"] {
+            JsonValue::Null => vec![],
+            price_schedule_json => parse_run_lengths(&as_string(price_schedule_json)?)?
+        };
+        if price_schedule.len() > SEASON_LENGTH {
+            bail!("Invalid price schedule for {}: longer than a season", name);
+        }
+        let costs = Costs::try_parse(&value_obj["costs"])?;
+
+        let storage_life = match &value_obj["storage_life"] {
+            JsonValue::Null => 0,
+            storage_life_json => as_usize(storage_life_json)?
+        };
+        let spoilage = match &value_obj["spoilage"] {
+            JsonValue::Null => 0.0,
+            spoilage_json => as_f64(spoilage_json)?
+        };
+        if !(0.0..=1.0).contains(&spoilage) {
+            bail!("Invalid spoilage for {}: must be between 0 and 1", name);
+        }
 
         let mut harvestable_by_week = vec![false; SEASON_LENGTH];
         for planting_week in 0..SEASON_LENGTH {
@@ -98,49 +246,120 @@ impl Variety {
         }
 
         Ok(Variety {
-            name: String::from(name),
-            requirements: requirements,
-            planting_schedule: planting_schedule,
-            harvest_schedule: harvest_schedule,
-            instructions: instructions,
-            value_per_unit: value_per_unit,
-            harvestable_by_week: harvestable_by_week
+            name,
+            family,
+            requirements,
+            avoid_after,
+            prefer_after,
+            planting_schedule,
+            harvest_schedule,
+            instructions,
+            labour,
+            propagation,
+            turnaround,
+            soil_benefit,
+            risk,
+            value_per_unit,
+            price_schedule,
+            costs,
+            storage_life,
+            spoilage,
+            demand,
+            harvestable_by_week
         })
     }
 }
 
-fn try_parse_instructions(input: &JsonValue) -> Result<HashMap<String, String>, Box<dyn Error>> {
+// Parse a list of numbers, each of which may be followed by a run length,
+// e.g. "0:3,4,5:2" is [0, 0, 0, 4, 5, 5]
+pub fn parse_run_lengths(input: &str) -> Result<Vec<i32>, Box<dyn Error>> {
+    lazy_static! {
+        static ref REGEX_RUN_LENGTHS: Regex = Regex::new("([0-9]+)(:([0-9]+))?").unwrap();
+    }
+
+    let mut result = vec![];
+    for cap in REGEX_RUN_LENGTHS.captures_iter(input) {
+        match cap.get(3) {
+            None => {
+                let value = &cap[1].parse::<i32>()?;
+                result.push(*value);
+            },
+            Some(_) => {
+                let value = &cap[1].parse::<i32>()?;
+                let run_length = &cap[3].parse::<i32>()?;
+                for _ in 0..*run_length {
+                    result.push(*value);
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+// The instruction texts and the labour estimates, both keyed by week offset or "harvest"
+type Instructions = (HashMap<String, String>, HashMap<String, Labour>);
+
+// Each instruction is either its text, or an object with its "text" and an estimate of the
+// time it takes in "minutes" per bed or, for the harvest instruction, in "minutes_per_unit"
+fn try_parse_instructions(input: &JsonValue) -> Result<Instructions, Box<dyn Error>> {
     let input_obj = as_object(input)?;
 
-    let mut result = HashMap::new();
+    let mut instructions = HashMap::new();
+    let mut labour = HashMap::new();
 
-    for item in input_obj.iter() {
-        let key = std::string::String::from(item.0);
-        let val = as_string(item.1)?;
-        result.insert(key, std::string::String::from(val));
+    for (key, value) in input_obj.iter() {
+        if !value.is_object() {
+            instructions.insert(key.to_string(), as_string(value)?);
+            continue;
+        }
+
+        instructions.insert(key.to_string(), as_string(&value["text"])?);
+        match (&value["minutes"], &value["minutes_per_unit"]) {
+            (JsonValue::Null, JsonValue::Null) => (),
+            (minutes, JsonValue::Null) => {
+                labour.insert(key.to_string(), Labour::PerBed(as_f64(minutes)?));
+            },
+            (JsonValue::Null, minutes) if key == "harvest" => {
+                labour.insert(key.to_string(), Labour::PerUnit(as_f64(minutes)?));
+            },
+            _ => bail!("Invalid instruction {}: give minutes, or minutes_per_unit for the harvest", key)
+        }
     }
 
-    Ok(result)
+    Ok((instructions, labour))
 }
 
 #[cfg(test)]
 #[test]
+#[allow(clippy::bool_assert_comparison)]
 fn variety_from_json() {
     let js = json::parse(r#"
 {
     "name": "tomato",
+    "family": "Solanaceae",
     "requirements": [ "polytunnel" ],
+    "avoid_after": [ "Solanaceae" ],
+    "prefer_after": [ "clover" ],
     "harvest_schedule": "0:3,4,5:2",
     "planting_schedule": "4-8,20-24,40,50",
     "instructions": {
         "-6": "Seed <variety> into a 64 tray and label it <label>",
         "-4": "Transplant <variety> from tray <label> into 20cm pots and label them <label>",
-        "0": "Transplant <variety> from pots labelled <label> into bed <bed>"
+        "0": { "text": "Transplant <variety> from pots labelled <label> into bed <bed>", "minutes": 45 },
+        "harvest": { "text": "Harvest <units> units of <variety> from bed <bed>", "minutes_per_unit": 0.5 }
     },
-    "value_per_unit": 100
+    "propagation": { "tray": "72", "trays_per_bed": 4 },
+    "risk": { "yield_deviation": 0.25, "failure_probability": 0.1 },
+    "value_per_unit": 100,
+    "costs": { "seed": 30, "harvest_unit": 5 },
+    "demand": "80:10,120:20,60:2",
+    "price_schedule": "300:10,100:20"
 }"#).expect("test is wrong");
-    let variety = Variety::try_parse(&js).expect("failed to parse");
+    let variety = Variety::try_parse(&js, None).expect("failed to parse");
     assert_eq!(variety.name, "tomato");
+    assert_eq!(variety.family.as_deref(), Some("Solanaceae"));
+    assert!(variety.is_avoided_after(&variety));
+    assert!(!variety.is_preferred_after(&variety));
     assert!(variety.requirements.contains(&String::from("polytunnel")));
     assert!(!variety.requirements.contains(&String::from("magic")));
     assert_eq!(variety.harvest_schedule, vec![0,0,0,4,5,5]);
@@ -157,7 +376,20 @@ fn variety_from_json() {
     assert_eq!(variety.planting_schedule[41], false);
     assert_eq!(variety.instructions["-6"], "Seed <variety> into a 64 tray and label it <label>");
     assert_eq!(variety.instructions["0"], "Transplant <variety> from pots labelled <label> into bed <bed>");
+    assert_eq!(variety.labour["0"], Labour::PerBed(45.0));
+    assert_eq!(variety.labour["harvest"], Labour::PerUnit(0.5));
+    assert!(!variety.labour.contains_key("-6"));
+    assert_eq!(variety.propagation, Some(Propagation{ tray: "72".to_string(), trays_per_bed: 4.0 }));
+    assert_eq!(variety.get_nursery_weeks(), 6);
+    assert_eq!(variety.risk, YieldRisk{ deviation: 0.25, failure_probability: 0.1 });
     assert_eq!(variety.value_per_unit, 100);
+    assert_eq!(variety.costs, Costs{ seed: 30, planting: 0, harvest_unit: 5, bed_week: 0 });
+    assert_eq!(variety.demand.len(), 32);
+    assert_eq!(variety.demand[9..11], [80, 120]);
+    assert_eq!(variety.get_price(9), 300);
+    assert_eq!(variety.get_price(10), 100);
+    assert_eq!(variety.get_price(30), 100);
+    assert_eq!(variety.get_price(61), 300);
     assert_eq!(variety.harvestable_by_week[0], false);
     assert_eq!(variety.harvestable_by_week[1], true);
 }
@@ -167,11 +399,24 @@ impl Variety {
     pub fn empty() -> Self {
         Variety{
             name: "".to_string(),
+            family: None,
             harvest_schedule: vec![],
             planting_schedule: [false;SEASON_LENGTH],
             instructions: std::collections::HashMap::new(),
+            labour: std::collections::HashMap::new(),
+            propagation: None,
+            turnaround: 0,
+            soil_benefit: 0,
+            risk: YieldRisk::default(),
             requirements: vec![],
+            avoid_after: vec![],
+            prefer_after: vec![],
             value_per_unit: 100,
+            price_schedule: vec![],
+            costs: Costs::default(),
+            storage_life: 0,
+            spoilage: 0.0,
+            demand: vec![],
             harvestable_by_week: vec![false; SEASON_LENGTH]
         }
     }
@@ -180,21 +425,77 @@ impl Variety {
     pub fn dummy(name: &str, reqs: Vec<&str>) -> Self {
         Variety{
             name: name.to_string(),
+            family: None,
             harvest_schedule: vec![],
             planting_schedule: [true;SEASON_LENGTH],
             instructions: std::collections::HashMap::new(),
+            labour: std::collections::HashMap::new(),
+            propagation: None,
+            turnaround: 0,
+            soil_benefit: 0,
+            risk: YieldRisk::default(),
             requirements: reqs.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+            avoid_after: vec![],
+            prefer_after: vec![],
             value_per_unit: 100,
+            price_schedule: vec![],
+            costs: Costs::default(),
+            storage_life: 0,
+            spoilage: 0.0,
+            demand: vec![],
             harvestable_by_week: vec![true; SEASON_LENGTH]
         }
     }
 
+    // Weeks from the earliest instruction to planting out, which is how long the
+    // variety spends in the nursery
+    pub fn get_nursery_weeks(&self) -> WeekRange {
+        self.instructions
+            .keys()
+            .filter_map(|k| k.parse::<i32>().ok())
+            .filter(|&offset| (-52..0).contains(&offset))
+            .map(|offset| -offset as WeekRange)
+            .max()
+            .unwrap_or(0)
+    }
+
+    // A crop grown for the soil rather than for sale, which is never harvested
+    pub fn is_cover_crop(&self) -> bool {
+        self.harvest_schedule.iter().all(|&units| units == 0)
+    }
+
     // Get how long the crop lasts from planting out to last harvest
     pub fn get_longevity(&self) -> WeekRange {
-        return self.harvest_schedule.len();
+        self.harvest_schedule.len()
+    }
+
+    // The price per unit in the given week
+    pub fn get_price(&self, week: usize) -> i32 {
+        match self.price_schedule.get(week % SEASON_LENGTH) {
+            Some(&price) => price,
+            None => self.value_per_unit
+        }
     }
 
     pub fn is_harvestable_in_week(&self, week: usize) -> bool {
         self.harvestable_by_week[week%SEASON_LENGTH]
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+#[test]
+fn variety_planting_schedule_from_frost_dates() {
+    let js = json::parse(r#"
+{
+    "name": "tomato",
+    "harvest_schedule": "0:3,4,5:2",
+    "planting_schedule": "frost+2..frost+4",
+    "instructions": {},
+    "value_per_unit": 100
+}"#).expect("test is wrong");
+    assert!(Variety::try_parse(&js, None).is_err());
+    let frost = FrostDates{ last_spring: 15, first_autumn: 42 };
+    let variety = Variety::try_parse(&js, Some(&frost)).expect("failed to parse");
+    let weeks = (0..SEASON_LENGTH).filter(|&w| variety.planting_schedule[w]).collect::<Vec<_>>();
+    assert_eq!(weeks, vec![17, 18, 19]);
+}
@@ -0,0 +1,68 @@
+use crate::plan::Plan;
+use crate::params::Params;
+use crate::evaluator::Evaluator;
+use crate::constant::SEASON_LENGTH;
+use core::fmt::Display;
+
+const CHART_WIDTH: f64 = 50.0;
+
+// Charts the hours of work the plan takes each week against the labour available
+// Hours beyond the capacity are drawn with '!'
+pub struct WorkloadReport<'a> {
+    params: &'a Params,
+    plan: &'a Plan
+}
+
+impl<'a> WorkloadReport<'a> {
+    pub fn new(params: &'a Params, plan: &'a Plan) -> WorkloadReport<'a> {
+        WorkloadReport{
+            params,
+            plan
+        }
+    }
+}
+
+impl<'a> Display for WorkloadReport<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let hours = Evaluator::new(self.params, self.plan)
+            .get_workload()
+            .iter()
+            .map(|minutes| minutes / 60.0)
+            .collect::<Vec<_>>();
+        let capacity = self.params.labour.as_ref().map(|l| &l.hours);
+
+        let max_hours = hours
+            .iter()
+            .chain(capacity.into_iter().flatten())
+            .fold(0.0_f64, |a, &b| a.max(b));
+        if max_hours == 0.0 {
+            return writeln!(f, "No work has been estimated. Give the varieties' instructions minutes");
+        }
+        let scale = CHART_WIDTH / max_hours;
+
+        writeln!(f, "{:<6}{:>8}{:>10}  ", "Week", "Hours", "Capacity")?;
+        for week in 0..SEASON_LENGTH {
+            write!(f, "{:<6}{:>8.1}", week, hours[week])?;
+            let available = match capacity {
+                Some(capacity) => {
+                    write!(f, "{:>10.1}  ", capacity[week])?;
+                    capacity[week]
+                },
+                None => {
+                    write!(f, "{:>10}  ", "-")?;
+                    hours[week]
+                }
+            };
+            let within = (hours[week].min(available) * scale).round() as usize;
+            let over = (hours[week] * scale).round() as usize - within;
+            writeln!(f, "{}{}", "#".repeat(within), "!".repeat(over))?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "Total: {:.1} hours", hours.iter().sum::<f64>())?;
+        if let Some(labour) = &self.params.labour {
+            writeln!(f, "Overload: {:.1} hours", labour.get_overload(&Evaluator::new(self.params, self.plan).get_workload()))?;
+        }
+        Ok(())
+    }
+}